@@ -1,5 +1,3 @@
-mod parser;
-
 fn main() {
     println!("Hello, world!");
 }
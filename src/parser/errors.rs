@@ -1,7 +1,11 @@
+use crate::parser::span::Span;
 use std::fmt;
 
+#[derive(Clone)]
 pub struct LexerError {
     pub message: String,
+    // Region of the source the error refers to.
+    pub span: Span,
 }
 
 impl fmt::Display for LexerError {
@@ -15,3 +19,53 @@ impl fmt::Debug for LexerError {
         write!(f, "{}", self.message)
     }
 }
+
+// Lets CLI tools returning `io::Result` propagate lexer errors with `?`.
+#[cfg(feature = "std")]
+impl From<LexerError> for std::io::Error {
+    fn from(error: LexerError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error.message)
+    }
+}
+
+// Something legal but likely unintended (i.e: a redundant leading zero). Unlike errors, warnings
+// don't interrupt lexing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexerWarning {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for LexerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::parser::errors::LexerError;
+    use crate::parser::span::{Position, Span};
+    use std::io;
+
+    #[test]
+    fn test_into_io_error() {
+        let error = LexerError {
+            message: String::from("Unexpected backtick"),
+            span: Span {
+                start: Position { row: 0, column: 2 },
+                end: Position { row: 0, column: 3 },
+            },
+        };
+
+        let error = io::Error::from(error);
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Unexpected backtick");
+
+        let propagate = || -> io::Result<()> {
+            crate::parser::lexer::Lexer::new("`".chars()).tokenize()?;
+            Ok(())
+        };
+        assert_eq!(propagate().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
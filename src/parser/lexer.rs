@@ -1,27 +1,147 @@
-use crate::parser::errors::LexerError;
-use crate::parser::token::Token;
+use crate::parser::errors::{LexerError, LexerWarning};
+use crate::parser::reader::{ReaderChars, SourceError};
+use crate::parser::span::{Position, Span, SpannedToken, Trivia};
+use crate::parser::stream::TokenStream;
+use crate::parser::token::{escape, Token, TokenKind};
 use num_bigint::BigInt;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
 use std::iter::Peekable;
+#[cfg(feature = "std")]
+use std::path::Path;
+use std::rc::Rc;
 use std::str::FromStr;
 
-const SEMICOLON: char = ';';
 const DOUBLE_QUOTES: char = '"';
 const SINGLE_QUOTES: char = '\'';
 const UNDERSCORE: char = '_';
 const BYTES_PREFIX: char = 'b';
+const RAW_PREFIX: char = 'r';
+const FORMAT_PREFIX: char = 'f';
 const DOT_SEPERATOR: char = '.';
+const DECIMAL_SUFFIX: char = 'd';
+const HASH: char = '#';
+const BACKSLASH: char = '\\';
+const BACKTICK: char = '`';
+// Page breaks of sorts: whitespace that moves neither the row nor the column
+const FORM_FEED: char = '\u{c}';
+const VERTICAL_TAB: char = '\u{b}';
+// What lossy decoders substitute for bytes they couldn't decode
+const REPLACEMENT_CHAR: char = '\u{fffd}';
+const REPLACEMENT_CHAR_ERROR: &str =
+    "Unexpected U+FFFD replacement character (was the source decoded lossily?)";
+// Zero-width spaces and joiners, the word joiner and the byte order mark
+// Only skipped at the very start of the input, anywhere else it's a zero-width char like the rest
+const BYTE_ORDER_MARK: char = '\u{feff}';
+const ZERO_WIDTH_CHARS: [char; 5] = ['\u{200b}', '\u{200c}', '\u{200d}', '\u{2060}', BYTE_ORDER_MARK];
 
+enum Escape {
+    // \x escapes stand for a single byte, which is only a valid char when it's ASCII.
+    Byte(u8),
+    Char(char),
+}
+
+// What ends a statement. Either way the parser only ever sees `Token::Semicolon`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatementTerminator {
+    Semicolon,
+    // A newline ends the statement on its line, unless it's inside parentheses or square brackets,
+    // or there's nothing to terminate (e.g: an empty line, or one ending with `;` or `{`).
+    Newline,
+    // Explicit semicolons, plus ones inserted at newlines (outside of parentheses and square
+    // brackets) after a token that can end a statement, so "a +\n b" is still one statement.
+    Automatic,
+}
+
+// Where a tracing lexer writes what it lexes. Shared, so the caller can still read a buffer back.
+pub type TraceSink = Rc<RefCell<dyn Write>>;
+
+// How the brackets of some lexed input pair up, see `bracket_balance`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BracketBalance {
+    Balanced,
+    // This many brackets are still open (i.e: a REPL should keep reading lines)
+    Open(usize),
+    // The closing bracket at this index closes nothing, or a different kind of bracket
+    Unmatched(usize),
+}
+
+// A snapshot of everything a lexer needs to pick up again from some earlier point, see
+// `Lexer::checkpoint`.
+pub struct Checkpoint {
+    history_length: usize,
+    current_chr: Option<char>,
+    row: usize,
+    column: usize,
+    current_char_processed: bool,
+    token_start: Position,
+    token_end_override: Option<Position>,
+    lexeme: Option<String>,
+    at_line_start: bool,
+    indentation: String,
+    indent_stack: Vec<String>,
+    pending_tokens: VecDeque<SpannedToken>,
+    lookahead: VecDeque<Result<SpannedToken, LexerError>>,
+    trivia: Vec<Trivia>,
+    statement_open: bool,
+    bracket_depth: usize,
+    failed: bool,
+    warnings_length: usize,
+    // Keeps the lexer recording consumed chars for as long as the checkpoint is alive
+    _alive: Rc<()>,
+}
+
+// Tracks how many checkpoints of a lexer are alive. A clone of the lexer starts out with none, or
+// both lexers would keep recording consumed chars for good.
+struct LiveCheckpoints(Rc<()>);
+
+impl Clone for LiveCheckpoints {
+    fn clone(&self) -> Self {
+        LiveCheckpoints(Rc::new(()))
+    }
+}
+
+// Cloning (only possible when the input is) gives an independent lexer that continues from the
+// same point, e.g: for speculative parsing.
+#[derive(Clone)]
 pub struct Lexer<T: Iterator<Item = char>> {
     input: Peekable<T>,
     current_chr: Option<char>,
-    previous_chr: Option<char>,
     row: usize,
     column: usize,
     identifiers: HashMap<String, Token>,
-    operators: Vec<char>,
-    delimiters: Vec<char>,
     current_char_processed: bool,
+    source_error: Option<SourceError>,
+    token_start: Position,
+    token_end_override: Option<Position>,
+    tab_width: usize,
+    preserve_raw_lexemes: bool,
+    lexeme: Option<String>,
+    concat_adjacent_strings: bool,
+    layout_mode: bool,
+    at_line_start: bool,
+    indentation: String,
+    indent_stack: Vec<String>,
+    pending_tokens: VecDeque<SpannedToken>,
+    // Results lexed ahead by `peek_n`, handed out before lexing any further
+    lookahead: VecDeque<Result<SpannedToken, LexerError>>,
+    collect_trivia: bool,
+    trivia: Vec<Trivia>,
+    checkpoints: LiveCheckpoints,
+    history: Vec<char>,
+    replay: VecDeque<char>,
+    statement_terminator: StatementTerminator,
+    statement_open: bool,
+    bracket_depth: usize,
+    errors_are_fatal: bool,
+    failed: bool,
+    case_insensitive_keywords: bool,
+    ascii_whitespace: bool,
+    warnings: Vec<LexerWarning>,
+    reject_replacement_chars: bool,
+    max_identifier_length: Option<usize>,
+    trace: Option<TraceSink>,
 }
 
 fn get_identifiers_map() -> HashMap<String, Token> {
@@ -33,6 +153,10 @@ fn get_identifiers_map() -> HashMap<String, Token> {
     identifiers.insert(String::from("else"), Token::Else);
     identifiers.insert(String::from("for"), Token::For);
     identifiers.insert(String::from("while"), Token::While);
+    identifiers.insert(String::from("unless"), Token::Unless);
+    identifiers.insert(String::from("until"), Token::Until);
+    identifiers.insert(String::from("when"), Token::When);
+    identifiers.insert(String::from("defer"), Token::Defer);
     identifiers.insert(String::from("class"), Token::Class);
     identifiers.insert(String::from("fn"), Token::Function);
     identifiers.insert(String::from("priv"), Token::Private);
@@ -43,38 +167,149 @@ fn get_identifiers_map() -> HashMap<String, Token> {
     identifiers.insert(String::from("construct"), Token::Constructor);
     identifiers.insert(String::from("destruct"), Token::Destructor);
     identifiers.insert(String::from("super"), Token::Super);
+    identifiers.insert(String::from("virtual"), Token::Virtual);
+    identifiers.insert(String::from("override"), Token::Override);
+    identifiers.insert(String::from("abstract"), Token::Abstract);
+    identifiers.insert(String::from("static"), Token::Static);
+    identifiers.insert(String::from("global"), Token::Global);
+    identifiers.insert(String::from("nonlocal"), Token::Nonlocal);
     identifiers.insert(String::from("return"), Token::Return);
+    identifiers.insert(String::from("assert"), Token::Assert);
+    identifiers.insert(String::from("yield"), Token::Yield);
+    identifiers.insert(String::from("typeof"), Token::Typeof);
+    identifiers.insert(String::from("sizeof"), Token::Sizeof);
 
     // Literal values
     identifiers.insert(String::from("true"), Token::BoolValue { value: true });
     identifiers.insert(String::from("false"), Token::BoolValue { value: false });
     identifiers.insert(String::from("null"), Token::NullValue);
 
+    // Word forms of the logical operators
+    identifiers.insert(String::from("and"), Token::LogicalAnd);
+    identifiers.insert(String::from("or"), Token::LogicalOr);
+    identifiers.insert(String::from("not"), Token::Not);
+
     // Builtin types
-    identifiers.insert(String::from("int"), Token::IntType);
-    identifiers.insert(String::from("float"), Token::FloatType);
-    identifiers.insert(String::from("str"), Token::StringType);
-    identifiers.insert(String::from("char"), Token::CharType);
-    identifiers.insert(String::from("bool"), Token::BoolType);
-    identifiers.insert(String::from("bytes"), Token::BytesType);
-    identifiers.insert(String::from("tuple"), Token::TupleType);
-    identifiers.insert(String::from("enum"), Token::EnumType);
-    identifiers.insert(String::from("list"), Token::ListType);
-    identifiers.insert(String::from("dict"), Token::DictType);
+    for (name, token) in get_builtin_types() {
+        identifiers.insert(String::from(name), token);
+    }
 
     identifiers
 }
 
-fn get_operators() -> Vec<char> {
+fn get_builtin_types() -> Vec<(&'static str, Token)> {
     vec![
-        '+', '-', '*', '/', '%', '!', '=', '|', '&', '^', '<', '>', '~',
+        ("int", Token::IntType),
+        ("float", Token::FloatType),
+        ("str", Token::StringType),
+        ("char", Token::CharType),
+        ("bool", Token::BoolType),
+        ("bytes", Token::BytesType),
+        ("tuple", Token::TupleType),
+        ("enum", Token::EnumType),
+        ("list", Token::ListType),
+        ("dict", Token::DictType),
     ]
 }
 
-fn get_delimiters() -> Vec<char> {
-    vec![
-        '{', '}', '[', ']', '(', ')', ',', ';', ':', '.', 
-    ]
+// Every word lexed as something other than a symbol, sorted so the order doesn't depend on the
+// map's hashing.
+pub fn keywords() -> Vec<String> {
+    let mut keywords: Vec<String> = get_identifiers_map().into_keys().collect();
+    keywords.sort();
+    keywords
+}
+
+// Just the keywords naming a builtin type (i.e: for completing type positions).
+pub fn builtin_types() -> Vec<&'static str> {
+    get_builtin_types().into_iter().map(|(name, _)| name).collect()
+}
+
+// Whether lexed input (i.e: a REPL line) ends a statement, with a `;` or a closing `}`. A trailing
+// `Eof` is ignored.
+pub fn ends_with_terminator(tokens: &[Token]) -> bool {
+    matches!(
+        tokens.iter().rev().find(|token| **token != Token::Eof),
+        Some(Token::Semicolon) | Some(Token::RightCurlyBracket)
+    )
+}
+
+// Pairs up parentheses, square and curly brackets. Since it works on tokens, brackets inside
+// strings and comments don't count.
+pub fn bracket_balance(tokens: &[Token]) -> BracketBalance {
+    let mut expected_closers: Vec<Token> = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LeftParens => expected_closers.push(Token::RightParens),
+            Token::LeftSquareBracket | Token::HashBracket => {
+                expected_closers.push(Token::RightSquareBracket)
+            }
+            Token::LeftCurlyBracket => expected_closers.push(Token::RightCurlyBracket),
+            Token::RightParens | Token::RightSquareBracket | Token::RightCurlyBracket => {
+                let expected = expected_closers.pop();
+
+                if expected.as_ref() != Some(token) {
+                    return BracketBalance::Unmatched(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match expected_closers.len() {
+        0 => BracketBalance::Balanced,
+        open => BracketBalance::Open(open),
+    }
+}
+
+// Moves a span lexed on its own (i.e: a format string hole) to where its source starts.
+fn offset_span(span: Span, origin: Position) -> Span {
+    let offset = |position: Position| Position {
+        row: origin.row + position.row,
+        column: if position.row == 0 { origin.column + position.column } else { position.column },
+    };
+
+    Span {
+        start: offset(span.start),
+        end: offset(span.end),
+    }
+}
+
+// Values, closing brackets and the keywords that make up a statement on their own, i.e: tokens
+// after which a newline ends the statement in `StatementTerminator::Automatic` mode.
+fn can_end_statement(token: &Token) -> bool {
+    token.is_type()
+        || matches!(
+            token,
+            Token::Symbol { .. }
+                | Token::IntValue { .. }
+                | Token::FloatValue { .. }
+                | Token::DecimalValue { .. }
+                | Token::StringValue { .. }
+                | Token::CharValue { .. }
+                | Token::BoolValue { .. }
+                | Token::BytesValue { .. }
+                // The whole format string is lexed along with its start
+                | Token::FormatStringStart
+                | Token::NullValue
+                | Token::SelfInstance
+                | Token::Super
+                | Token::Underscore
+                | Token::Return
+                | Token::Yield
+                | Token::RightParens
+                | Token::RightSquareBracket
+                | Token::RightCurlyBracket
+        )
+}
+
+fn is_operator_char(chr: char) -> bool {
+    matches!(chr, '+' | '-' | '*' | '/' | '%' | '!' | '=' | '|' | '&' | '^' | '<' | '>' | '~' | '?')
+}
+
+fn is_delimiter_char(chr: char) -> bool {
+    matches!(chr, '{' | '}' | '[' | ']' | '(' | ')' | ',' | ';' | ':' | '.' | '$')
 }
 
 impl<T> Lexer<T>
@@ -85,703 +320,4751 @@ where
         Lexer {
             input: input.peekable(),
             current_chr: None,
-            previous_chr: None,
             row: 0,
             column: 0,
             identifiers: get_identifiers_map(),
-            operators: get_operators(),
-            delimiters: get_delimiters(),
-            current_char_processed: true
+            current_char_processed: true,
+            source_error: None,
+            token_start: Position { row: 0, column: 0 },
+            token_end_override: None,
+            tab_width: 1,
+            preserve_raw_lexemes: false,
+            lexeme: None,
+            concat_adjacent_strings: false,
+            layout_mode: false,
+            at_line_start: true,
+            indentation: String::from(""),
+            indent_stack: vec![String::from("")],
+            pending_tokens: VecDeque::new(),
+            lookahead: VecDeque::new(),
+            collect_trivia: false,
+            trivia: Vec::new(),
+            checkpoints: LiveCheckpoints(Rc::new(())),
+            history: Vec::new(),
+            replay: VecDeque::new(),
+            statement_terminator: StatementTerminator::Semicolon,
+            statement_open: false,
+            bracket_depth: 0,
+            errors_are_fatal: false,
+            failed: false,
+            case_insensitive_keywords: false,
+            ascii_whitespace: false,
+            warnings: Vec::new(),
+            reject_replacement_chars: false,
+            max_identifier_length: None,
+            trace: None,
         }
     }
 
-    // todo: implement as iterator
-    pub fn next_token(&mut self) -> Result<Token, LexerError> {
-        if self.current_char_processed {
-            self.next_char();
-        }
-        else {
-            self.current_char_processed = true;
-        }
-        
-        self.skip_redundant_characters();
-
-        if self.current_chr.is_none() {
-            return Err(LexerError {
-                message: String::from("No more tokens"),
-            });
-        }
-
-        if self.is_letter() || self.char_equals(UNDERSCORE) {
-            return self.handle_identifier();
-        }
-
-        if self.is_digit() {
-            return self.handle_number();
-        }
-
-        if self.is_beginning_of_string() {
-            return self.handle_string();
-        }
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
 
-        if self.is_beginning_of_char() {
-            return self.handle_char();
-        }
+    // Merges string literals separated only by whitespace or comments into a single `StringValue`.
+    pub fn with_adjacent_string_concatenation(mut self, concat_adjacent_strings: bool) -> Self {
+        self.concat_adjacent_strings = concat_adjacent_strings;
+        self
+    }
 
-        if self.is_operator() {
-            return self.handle_operator();
-        }
+    // Emits `Indent`/`Dedent` tokens whenever the indentation of a line opens or closes a block.
+    pub fn with_layout_mode(mut self, layout_mode: bool) -> Self {
+        self.layout_mode = layout_mode;
+        self
+    }
 
-        if self.is_delimiter() {
-            return self.handle_delimiter();
-        }
+    // Keeps the exact source text of every token in `SpannedToken::raw` (e.g: "0x0A" rather than 10).
+    pub fn with_raw_lexemes(mut self, preserve_raw_lexemes: bool) -> Self {
+        self.preserve_raw_lexemes = preserve_raw_lexemes;
+        self
+    }
 
-        Err(LexerError {
-            message: String::from("Failed to lex source"),
-        })
+    // Keeps skipped comments and blank lines in `SpannedToken::leading_trivia` of the token after them.
+    pub fn with_trivia(mut self, collect_trivia: bool) -> Self {
+        self.collect_trivia = collect_trivia;
+        self
     }
 
-    fn next_char(&mut self) {
-        self.previous_chr = self.current_chr;
-        self.current_chr = self.input.next();
+    // By default lexing carries on past an error, skipping the char it failed at. Fatal errors end the
+    // token stream instead, as if the input stopped there.
+    pub fn errors_are_fatal(mut self, errors_are_fatal: bool) -> Self {
+        self.errors_are_fatal = errors_are_fatal;
+        self
+    }
 
-        if self.current_chr.is_some() {
-            self.column += 1;
-        }
+    // Matches keywords regardless of case (i.e: "IF" and "If" are both `Token::If`). Symbols still
+    // keep the case they were written in.
+    pub fn with_case_insensitive_keywords(mut self, case_insensitive_keywords: bool) -> Self {
+        self.case_insensitive_keywords = case_insensitive_keywords;
+        self
     }
 
-    fn is_whitespace(&self) -> bool {
-        self.current_chr.unwrap().is_whitespace()
+    // Only ASCII whitespace (spaces, tabs, line breaks, form feeds and vertical tabs) is skipped, so
+    // other (Unicode) spaces that slipped into the source are errors rather than silently skipped.
+    pub fn with_ascii_whitespace(mut self, ascii_whitespace: bool) -> Self {
+        self.ascii_whitespace = ascii_whitespace;
+        self
     }
 
-    fn is_newline(&mut self) -> bool {
-        return match self.current_chr {
-            Some('\n') => true,
-            Some('\r') => {
-                return match self.input.peek() {
-                    Some('\n') => {
-                        self.next_char();
-                        return true;
-                    }
-                    _ => false,
-                };
-            }
-            _ => false,
-        };
+    // Outside of literals U+FFFD is always an error. This also rejects it inside string and char
+    // literals, for sources that went through a lossy decoder (where it stands for invalid bytes).
+    pub fn with_replacement_char_rejection(mut self, reject_replacement_chars: bool) -> Self {
+        self.reject_replacement_chars = reject_replacement_chars;
+        self
     }
 
-    fn is_alphanumeric(&self) -> bool {
-        self.current_chr.unwrap().is_ascii_alphanumeric()
+    // Longer identifiers (raw ones included) are errors, so a single huge "word" can't take up an
+    // unbounded amount of memory. Unlimited by default.
+    pub fn with_max_identifier_length(mut self, max_identifier_length: usize) -> Self {
+        self.max_identifier_length = Some(max_identifier_length);
+        self
     }
 
-    fn is_letter(&self) -> bool {
-        self.current_chr.unwrap().is_ascii_alphabetic()
+    // Writes a line per token (or error) lexed to `sink`, with its span, for debugging the lexer or
+    // a grammar. Failing to write doesn't affect lexing.
+    pub fn with_trace(mut self, sink: TraceSink) -> Self {
+        self.trace = Some(sink);
+        self
     }
 
-    fn is_beginning_of_string(&self) -> bool {
-        self.char_equals(DOUBLE_QUOTES)
+    pub fn with_statement_terminator(mut self, statement_terminator: StatementTerminator) -> Self {
+        self.statement_terminator = statement_terminator;
+        self
     }
 
-    fn is_beginning_of_char(&self) -> bool {
-        self.char_equals(SINGLE_QUOTES)
+    pub fn next_token(&mut self) -> Result<Token, LexerError> {
+        self.next_spanned_token().map(|spanned| spanned.token)
     }
 
-    fn is_digit(&self) -> bool {
-        self.current_chr.unwrap().is_ascii_digit()
+    // Like `next_token`, but reports errors as the embedder's own error type.
+    pub fn next_token_as<E: From<LexerError>>(&mut self) -> Result<Token, E> {
+        self.next_token().map_err(E::from)
     }
 
-    fn is_operator(&self) -> bool {
-        self.operators.contains(&self.current_chr.unwrap())
+    // Once the input is exhausted, every call returns `Token::Eof`.
+    pub fn next_spanned_token(&mut self) -> Result<SpannedToken, LexerError> {
+        self.try_next_spanned().unwrap_or_else(|| Ok(self.zero_width_token(Token::Eof)))
     }
 
-    fn is_delimiter(&self) -> bool {
-        self.delimiters.contains(&self.current_chr.unwrap())
+    // Like `next_token`, but returns `None` (rather than `Token::Eof`) once the input is exhausted.
+    pub fn try_next(&mut self) -> Option<Result<Token, LexerError>> {
+        self.try_next_spanned().map(|result| result.map(|spanned| spanned.token))
     }
 
-    fn char_equals(&self, compared_char: char) -> bool {
-        self.current_chr.unwrap() == compared_char
+    pub fn try_next_spanned(&mut self) -> Option<Result<SpannedToken, LexerError>> {
+        if let Some(peeked) = self.lookahead.pop_front() {
+            return Some(peeked);
+        }
+
+        self.lex_ahead()
     }
 
-    fn skip_redundant_characters(&mut self) {
-        while self.current_chr.is_some() && (self.is_whitespace() || self.is_newline()) {
-            if self.is_newline() {
-                self.row += 1;
-                self.column = 0;
-            } else {
-                self.column += 1;
+    // Lexes up to `k` tokens ahead without consuming them, returning fewer once the input is
+    // exhausted (`Eof` isn't included). An error among them is returned instead, but stays buffered
+    // so that it's still handed out in its place, after the tokens before it.
+    pub fn peek_n(&mut self, k: usize) -> Result<Vec<&Token>, LexerError> {
+        while self.lookahead.len() < k && !self.lookahead.iter().any(Result::is_err) {
+            match self.lex_ahead() {
+                Some(result) => self.lookahead.push_back(result),
+                None => break,
             }
+        }
 
-            self.next_char();
+        self.lookahead
+            .iter()
+            .take(k)
+            .map(|result| result.as_ref().map(|spanned| &spanned.token).map_err(Clone::clone))
+            .collect()
+    }
+
+    fn lex_ahead(&mut self) -> Option<Result<SpannedToken, LexerError>> {
+        if self.failed {
+            return None;
+        }
+
+        let result = self.lex_next_spanned();
+
+        if self.errors_are_fatal && matches!(result, Some(Err(_))) {
+            self.failed = true;
+        }
+
+        if let (Some(sink), Some(result)) = (self.trace.as_ref(), result.as_ref()) {
+            let (span, traced) = match result {
+                Ok(spanned) => {
+                    let traced = format!("{:?} {}", spanned.token.kind(), spanned.token.to_source());
+                    (spanned.span, String::from(traced.trim_end()))
+                }
+                Err(err) => (err.span, format!("error: {}", err.message)),
+            };
+
+            let _ = writeln!(
+                sink.borrow_mut(),
+                "{}:{}-{}:{} {}",
+                span.start.row, span.start.column, span.end.row, span.end.column, traced
+            );
         }
+
+        result
     }
 
-    fn handle_identifier(&mut self) -> Result<Token, LexerError> {
-        let mut identifier = String::from("");
+    fn lex_next_spanned(&mut self) -> Option<Result<SpannedToken, LexerError>> {
+        if let Some(pending) = self.pending_tokens.pop_front() {
+            return Some(Ok(pending));
+        }
 
-        // Loop until end of word
-        while self.current_chr.is_some() && (self.is_alphanumeric() || self.char_equals(UNDERSCORE)) {
-            identifier.push(self.current_chr.unwrap());
+        if self.current_char_processed {
             self.next_char();
         }
+        else {
+            self.current_char_processed = true;
+        }
 
-        self.current_char_processed = false;
+        if self.row == 0 && self.column == 0 && self.char_equals(BYTE_ORDER_MARK) {
+            self.next_char();
+            self.column = 0;
+        }
 
-        // Common identifiers (e.g: "if", "true", "int", "while", ...)
-        if self.identifiers.contains_key(&identifier) {
-            return Ok(self.identifiers.get(&identifier).unwrap().clone());
+        if self.is_beginning_of_shebang() {
+            self.skip_line();
         }
-        // Literal bytes value (i.e: b"h\x04\x12")
-        else if identifier.len() == 1
-            && self.previous_chr.unwrap() == BYTES_PREFIX
-            && self.current_chr.is_some()
-            && self.char_equals(DOUBLE_QUOTES)
-        {
-            self.current_char_processed = true;
+        
+        self.skip_redundant_characters();
 
-            identifier = String::from(""); // Reset identifier (i.e, remove the 'b' character).
+        // A newline that ended a statement
+        if let Some(terminator) = self.pending_tokens.pop_front() {
+            self.current_char_processed = false;
+            return Some(Ok(terminator));
+        }
 
-            self.next_char();
+        if self.current_chr.is_none() {
+            if let Some(message) = self.source_error.as_ref().and_then(|error| error.borrow_mut().take()) {
+                return Some(Err(LexerError {
+                    message,
+                    span: Span {
+                        start: self.current_position(),
+                        end: self.current_position(),
+                    },
+                }));
+            }
 
-            while self.current_chr.is_some() && !self.char_equals(DOUBLE_QUOTES) {
-                identifier.push(self.current_chr.unwrap());
-                self.next_char();
+            // So does the end of the input
+            if self.statement_terminator != StatementTerminator::Semicolon && self.statement_open {
+                self.statement_open = false;
+                return Some(Ok(self.zero_width_token(Token::Semicolon)));
             }
 
-            if !self.char_equals(DOUBLE_QUOTES) {
-                return Err(LexerError {
-                    message: String::from("Failed to parse bytes value: missing double-quotes"),
-                });
+            // Close every block that's still open at the end of the input
+            if self.layout_mode && self.indent_stack.len() > 1 {
+                self.indent_stack.pop();
+                return Some(Ok(self.zero_width_token(Token::Dedent)));
             }
 
-            self.next_char();
-            self.current_char_processed = false;
+            return None;
+        }
 
-            return Ok(Token::BytesValue {
-                value: identifier.as_bytes().to_vec(),
-            });
+        self.token_start = self.current_position();
+
+        if self.layout_mode && self.at_line_start {
+            self.at_line_start = false;
+
+            // Lines continued inside brackets don't open or close blocks
+            if self.bracket_depth > 0 {
+                self.indentation.clear();
+            } else if let Some(result) = self.handle_indentation() {
+                return Some(result);
+            }
         }
-        // Symbol names
-        else {
-            return Ok(Token::Symbol { name: identifier });
+
+        self.token_end_override = None;
+        self.lexeme = if self.preserve_raw_lexemes { Some(String::from("")) } else { None };
+
+        let token = match self.handle_token() {
+            Ok(token) => token,
+            Err(err) => return Some(Err(err)),
+        };
+        self.track_statement(&token);
+
+        let raw = self.lexeme.take().map(|mut lexeme| {
+            if self.current_char_processed {
+                lexeme.push(self.current_chr.unwrap());
+            }
+            lexeme
+        });
+
+        Some(Ok(SpannedToken {
+            token,
+            span: Span {
+                start: self.token_start,
+                end: self.token_end(),
+            },
+            raw,
+            leading_trivia: std::mem::take(&mut self.trivia),
+        }))
+    }
+
+    // Everything warned about so far, in source order.
+    pub fn warnings(&self) -> &[LexerWarning] {
+        &self.warnings
+    }
+
+    // Lexes the whole input, stopping at the first error. `Eof` isn't included.
+    pub fn tokenize(self) -> Result<Vec<Token>, LexerError> {
+        self.collect()
+    }
+
+    // Like `tokenize`, appending to an existing buffer. Tokens lexed before an error are kept.
+    pub fn lex_into(self, out: &mut Vec<Token>) -> Result<(), LexerError> {
+        for token in self {
+            out.push(token?);
         }
+
+        Ok(())
     }
 
-    fn handle_number(&mut self) -> Result<Token, LexerError> {
-        let mut number = String::from("");
+    // Number of tokens in the input, without keeping any of them. Unlike `Iterator::count` (which
+    // this shadows), it stops at the first error.
+    pub fn count(self) -> Result<usize, LexerError> {
+        let mut count = 0;
 
-        while self.current_chr.is_some() && (self.is_digit() || self.char_equals(DOT_SEPERATOR)) {
-            number.push(self.current_chr.unwrap());
-            self.next_char();
+        for token in self {
+            token?;
+            count += 1;
         }
 
-        self.current_char_processed = false;
+        Ok(count)
+    }
 
-        return match number.matches(DOT_SEPERATOR).count() {
-            1 => {
-                let parsed_number = number.parse::<f64>();
+    // Push-style counterpart to `tokenize`: hands each token to `f` as soon as it's lexed.
+    pub fn lex_each<F: FnMut(Token)>(self, mut f: F) -> Result<(), LexerError> {
+        for token in self {
+            f(token?);
+        }
 
-                if parsed_number.is_err() {
-                    return Err(LexerError {
-                        message: String::from("Could not parse float"),
-                    });
-                }
+        Ok(())
+    }
 
-                Ok(Token::FloatValue {
-                    value: parsed_number.unwrap(),
-                })
-            },
-            0 => {
-                let parsed_number = BigInt::from_str(&number);
+    pub fn lex_with_spans(mut self) -> Result<TokenStream, LexerError> {
+        let mut tokens: Vec<SpannedToken> = Vec::new();
 
-                if parsed_number.is_err() {
-                    return Err(LexerError {
-                        message: String::from("Could not parse int"),
-                    });
-                }
+        while let Some(spanned) = self.try_next_spanned() {
+            tokens.push(spanned?);
+        }
 
-                Ok(Token::IntValue {
-                    value: parsed_number.unwrap(),
-                })
-            },
-            _ => Err(LexerError {
-                message: String::from("Invalid number - too many dot seperators"),
-            })
-        };
+        Ok(TokenStream::new(tokens))
     }
 
-    fn handle_string(&mut self) -> Result<Token, LexerError> {
-        let mut string = String::from("");
+    // Like `lex_with_spans`, but keeps trivia and always ends with `Eof`, which holds whatever comes
+    // after the last token.
+    pub fn lex_tokens_with_trivia(self) -> Result<TokenStream, LexerError> {
+        let mut lexer = self.with_trivia(true);
+        let mut tokens: Vec<SpannedToken> = Vec::new();
 
-        self.next_char();
+        while let Some(spanned) = lexer.try_next_spanned() {
+            tokens.push(spanned?);
+        }
+        tokens.push(lexer.next_spanned_token()?);
 
-        while self.current_chr.is_some() && !self.char_equals(DOUBLE_QUOTES) {
-            string.push(self.current_chr.unwrap());
-            self.next_char();
+        Ok(TokenStream::new(tokens))
+    }
+
+    // Lexes up to the next top-level `;` (or the end of input), e.g: a single statement in a REPL.
+    pub fn lex_expression(&mut self, include_terminator: bool) -> Result<Vec<Token>, LexerError> {
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut depth: usize = 0;
+
+        loop {
+            let token = self.next_token()?;
+
+            match token {
+                Token::Eof => break,
+                Token::Semicolon if depth == 0 => {
+                    if include_terminator {
+                        tokens.push(token);
+                    }
+                    break;
+                }
+                Token::LeftParens | Token::LeftSquareBracket | Token::LeftCurlyBracket => depth += 1,
+                Token::RightParens | Token::RightSquareBracket | Token::RightCurlyBracket => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+
+            tokens.push(token);
         }
 
-        if !self.char_equals(DOUBLE_QUOTES) {
+        Ok(tokens)
+    }
+
+    // Consumes the next token, failing unless it equals `expected`.
+    pub fn expect(&mut self, expected: &Token) -> Result<(), LexerError> {
+        let spanned = self.next_spanned_token()?;
+
+        if &spanned.token != expected {
             return Err(LexerError {
-                message: String::from("Failed to parse string value: missing double-quotes"),
+                message: format!("Expected {:?}, found {:?}", expected, spanned.token),
+                span: spanned.span,
             });
         }
 
-        return Ok(Token::StringValue {
-            value: string
-        });
+        Ok(())
     }
 
-    fn handle_char(&mut self) -> Result<Token, LexerError> {
-        self.next_char();
+    // Consumes the next token, failing unless it's of the `expected` kind (whatever its payload).
+    pub fn expect_kind(&mut self, expected: TokenKind) -> Result<Token, LexerError> {
+        let spanned = self.next_spanned_token()?;
 
-        if self.current_chr.is_none() {
+        if spanned.token.kind() != expected {
             return Err(LexerError {
-                message: String::from("Failed to parse character value"),
+                message: format!("Expected {:?}, found {:?}", expected, spanned.token),
+                span: spanned.span,
             });
         }
-        else if self.current_chr.is_some() && self.char_equals(SINGLE_QUOTES) {
-            return Err(LexerError {
-                message: String::from("Character literal may only contain one codepoint"),
-            });
+
+        Ok(spanned.token)
+    }
+
+    // Skips tokens up to the first one matching `predicate` (i.e: a `;` to recover at), which is left
+    // to be lexed next. Reaching the end of the input stops skipping as well.
+    pub fn skip_until<F: Fn(&Token) -> bool>(&mut self, predicate: F) -> Result<(), LexerError> {
+        loop {
+            let spanned = self.next_spanned_token()?;
+
+            if spanned.token == Token::Eof {
+                return Ok(());
+            }
+
+            if predicate(&spanned.token) {
+                self.lookahead.push_front(Ok(spanned));
+                return Ok(());
+            }
         }
+    }
 
-        let chr = self.current_chr.unwrap();
-        
-        self.next_char(); 
+    // Since the input can't be rewound, every char consumed while a checkpoint is alive is kept
+    // until all of them are dropped (or restored), so long-lived checkpoints hold on to the source
+    // lexed since. Restoring a checkpoint invalidates any taken after it, and it only applies to the
+    // lexer it was taken from (not to clones of it).
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            history_length: self.history.len(),
+            current_chr: self.current_chr,
+            row: self.row,
+            column: self.column,
+            current_char_processed: self.current_char_processed,
+            token_start: self.token_start,
+            token_end_override: self.token_end_override,
+            lexeme: self.lexeme.clone(),
+            at_line_start: self.at_line_start,
+            indentation: self.indentation.clone(),
+            indent_stack: self.indent_stack.clone(),
+            pending_tokens: self.pending_tokens.clone(),
+            lookahead: self.lookahead.clone(),
+            trivia: self.trivia.clone(),
+            statement_open: self.statement_open,
+            bracket_depth: self.bracket_depth,
+            failed: self.failed,
+            warnings_length: self.warnings.len(),
+            _alive: Rc::clone(&self.checkpoints.0),
+        }
+    }
 
-        if self.current_chr.is_none() || (self.current_chr.is_some() && !self.char_equals(SINGLE_QUOTES)) {
-            return Err(LexerError {
-                message: String::from("Failed to parse character value: missing single-quotes"),
-            });
+    // Rolls back to the point `checkpoint` was taken at, so the same tokens are lexed again.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        for chr in self.history.drain(checkpoint.history_length..).rev() {
+            self.replay.push_front(chr);
         }
-        
-        return Ok(Token::CharValue {
-            value: chr
-        });
+
+        self.current_chr = checkpoint.current_chr;
+        self.row = checkpoint.row;
+        self.column = checkpoint.column;
+        self.current_char_processed = checkpoint.current_char_processed;
+        self.token_start = checkpoint.token_start;
+        self.token_end_override = checkpoint.token_end_override;
+        self.lexeme = checkpoint.lexeme;
+        self.at_line_start = checkpoint.at_line_start;
+        self.indentation = checkpoint.indentation;
+        self.indent_stack = checkpoint.indent_stack;
+        self.pending_tokens = checkpoint.pending_tokens;
+        self.lookahead = checkpoint.lookahead;
+        self.trivia = checkpoint.trivia;
+        self.statement_open = checkpoint.statement_open;
+        self.bracket_depth = checkpoint.bracket_depth;
+        self.failed = checkpoint.failed;
+        self.warnings.truncate(checkpoint.warnings_length);
     }
 
-    fn handle_operator(&mut self) -> Result<Token, LexerError> {
-        return match self.current_chr.unwrap() {
-            '+' => Ok(Token::Add),
-            '-' => {
-                return match self.input.peek() {
-                    Some('>') => {
-                        self.next_char();
-                        return Ok(Token::FnReturnTypeDelim);
-                    },
-                    _ => Ok(Token::Subtract)
-                }
-            },
-            '*' => Ok(Token::Multiply),
-            '/' => Ok(Token::Divide),
-            '%' => Ok(Token::Modulo),
-            '!' => {
-                return match self.input.peek() {
-                    Some('=') => {
-                        self.next_char();
-                        return Ok(Token::NotEquals);
-                    },
-                    _ => Ok(Token::Not)
-                }
-            },
-            '=' => {
-                return match self.input.peek() {
-                    Some('=') => {
-                        self.next_char();
-                        return Ok(Token::Equals);
-                    },
-                    _ => Ok(Token::Assignment)
-                }
-            },
-            '|' => {
-                return match self.input.peek() {
-                    Some('|') => {
-                        self.next_char();
-                        return Ok(Token::LogicalOr);
-                    },
-                    _ => Ok(Token::BitwiseOr)
-                }
-            },
-            '&' => {
-                return match self.input.peek() {
-                    Some('&') => {
-                        self.next_char();
-                        return Ok(Token::LogicalAnd);
-                    },
-                    _ => Ok(Token::BitwiseAnd)
-                }
-            },
-            '~' => Ok(Token::BitwiseNot),
-            '^' => Ok(Token::BitwiseXor),
-            '>' => {
-                return match self.input.peek() {
-                    Some('>') => {
-                        self.next_char();
-                        return Ok(Token::BitwiseRightShift);
-                    },
-                    Some('=') => {
-                        self.next_char();
-                        return Ok(Token::GreaterEqual);
-                    },
-                    _ => Ok(Token::Greater)
-                }
+    fn handle_token(&mut self) -> Result<Token, LexerError> {
+        if self.is_letter() || self.char_equals(UNDERSCORE) {
+            return self.handle_identifier();
+        }
+
+        if self.is_digit() {
+            return self.handle_number();
+        }
+
+        if self.is_beginning_of_string() {
+            return self.handle_string();
+        }
+
+        if self.is_beginning_of_char() {
+            return self.handle_char();
+        }
+
+        if self.is_operator() {
+            return self.handle_operator();
+        }
+
+        if self.is_delimiter() {
+            return self.handle_delimiter();
+        }
+
+        // Comments are skipped before getting here, so this is an attribute
+        if self.char_equals(HASH) {
+            self.next_char();
+            return Ok(Token::HashBracket);
+        }
+
+        // Only reachable in ASCII whitespace mode, for non-ASCII whitespace (i.e: U+00A0)
+        if self.current_chr.unwrap().is_whitespace() {
+            return Err(self.error("Unexpected non-ASCII whitespace"));
+        }
+
+        if self.char_equals(REPLACEMENT_CHAR) {
+            return Err(self.error(REPLACEMENT_CHAR_ERROR));
+        }
+
+        // Invisible, so they'd be confusing to report as anything else
+        if self.current_chr.is_some_and(|chr| ZERO_WIDTH_CHARS.contains(&chr)) {
+            return Err(self.error("Unexpected zero-width character"));
+        }
+
+        // Reserved, i.e: for template strings
+        if self.char_equals(BACKTICK) {
+            return Err(self.error("Unexpected backtick"));
+        }
+
+        Err(self.error("Failed to lex source"))
+    }
+
+    // Compares the indentation of a new line with the enclosing blocks, emitting an `Indent` when it
+    // opens a block and a `Dedent` for every block it closes. Indentation is compared textually (an
+    // inner block has to start with the exact indentation of its outer one), which is what catches
+    // blocks mixing tabs and spaces.
+    fn handle_indentation(&mut self) -> Option<Result<SpannedToken, LexerError>> {
+        let indentation = std::mem::take(&mut self.indentation);
+
+        if indentation == *self.indent_stack.last().unwrap() {
+            return None;
+        }
+
+        if indentation.starts_with(self.indent_stack.last().unwrap().as_str()) {
+            self.indent_stack.push(indentation);
+            self.current_char_processed = false;
+            return Some(Ok(self.zero_width_token(Token::Indent)));
+        }
+
+        while self.indent_stack.last().unwrap().starts_with(indentation.as_str())
+            && *self.indent_stack.last().unwrap() != indentation
+        {
+            self.indent_stack.pop();
+            let dedent = self.zero_width_token(Token::Dedent);
+            self.pending_tokens.push_back(dedent);
+        }
+
+        if *self.indent_stack.last().unwrap() != indentation {
+            self.pending_tokens.clear();
+
+            let error = if indentation.starts_with(self.indent_stack.last().unwrap().as_str()) {
+                self.error("Dedent does not match any outer indentation level")
+            } else {
+                self.error("Inconsistent use of tabs and spaces in indentation")
+            };
+
+            // The line's first token is still lexed after the error
+            self.current_char_processed = false;
+            return Some(Err(error));
+        }
+
+        self.pending_tokens.pop_front().map(|dedent| {
+            self.current_char_processed = false;
+            Ok(dedent)
+        })
+    }
+
+    // Keeps track of whether a newline would end a statement, unless terminated by semicolons only.
+    fn track_statement(&mut self, token: &Token) {
+        match token {
+            Token::LeftParens | Token::LeftSquareBracket | Token::HashBracket => self.bracket_depth += 1,
+            Token::RightParens | Token::RightSquareBracket => {
+                self.bracket_depth = self.bracket_depth.saturating_sub(1)
+            }
+            _ => {}
+        }
+
+        self.statement_open = match self.statement_terminator {
+            StatementTerminator::Automatic => can_end_statement(token),
+            _ => !matches!(token, Token::Semicolon | Token::LeftCurlyBracket),
+        };
+    }
+
+    // Layout tokens, `Eof` and inserted semicolons take up no space in the source.
+    fn zero_width_token(&mut self, token: Token) -> SpannedToken {
+        SpannedToken {
+            token,
+            span: Span {
+                start: self.current_position(),
+                end: self.current_position(),
             },
-            '<' => {
-                return match self.input.peek() {
-                    Some('<') => {
-                        self.next_char();
-                        return Ok(Token::BitwiseLeftShift);
-                    },
-                    Some('=') => {
-                        self.next_char();
-                        return Ok(Token::LessEqual);
-                    },
-                    _ => Ok(Token::Less)
-                }
+            raw: None,
+            leading_trivia: std::mem::take(&mut self.trivia),
+        }
+    }
+
+    // Like errors, warnings span the token being lexed so far.
+    fn warn(&mut self, message: &str) {
+        let span = Span {
+            start: self.token_start,
+            end: self.token_end(),
+        };
+
+        self.warnings.push(LexerWarning {
+            message: String::from(message),
+            span,
+        });
+    }
+
+    // Errors span from the start of the token being lexed up to where lexing stopped.
+    fn error(&self, message: &str) -> LexerError {
+        LexerError {
+            message: String::from(message),
+            span: Span {
+                start: self.token_start,
+                end: self.token_end(),
             },
-            _ => Err(LexerError {
-                message: String::from("Could not parse operator"),
-            })
+        }
+    }
+
+    // Position of the current character.
+    fn current_position(&self) -> Position {
+        Position {
+            row: self.row,
+            column: self.column,
+        }
+    }
+
+    // Position right after the last character of the token that was just lexed.
+    fn token_end(&self) -> Position {
+        if let Some(end) = self.token_end_override {
+            end
+        } else if self.current_char_processed && self.current_chr.is_some() {
+            Position {
+                row: self.row,
+                column: self.column + 1,
+            }
+        } else {
+            self.current_position()
+        }
+    }
+
+    fn next_char(&mut self) {
+        // A tab moves the column to the next tab stop, form feeds and vertical tabs not at all, and
+        // anything else by one.
+        match self.current_chr {
+            Some('\t') => self.column += self.tab_width - self.column % self.tab_width,
+            Some(FORM_FEED) | Some(VERTICAL_TAB) => {}
+            Some(_) => self.column += 1,
+            None => {}
+        }
+
+        if let (Some(lexeme), Some(chr)) = (self.lexeme.as_mut(), self.current_chr) {
+            lexeme.push(chr);
+        }
+
+        self.current_chr = match self.replay.pop_front() {
+            Some(chr) => Some(chr),
+            None => self.input.next(),
+        };
+
+        // Consumed chars only have to be kept while some checkpoint may still rewind past them
+        if Rc::strong_count(&self.checkpoints.0) > 1 {
+            if let Some(chr) = self.current_chr {
+                self.history.push(chr);
+            }
+        } else {
+            self.history.clear();
+        }
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        match self.replay.front() {
+            Some(&chr) => Some(chr),
+            None => self.input.peek().copied(),
+        }
+    }
+
+    // Looks `n` chars past the one `peek_char` returns, buffering whatever it has to read ahead.
+    fn peek_nth(&mut self, n: usize) -> Option<char> {
+        while self.replay.len() <= n {
+            self.replay.push_back(self.input.next()?);
+        }
+
+        Some(self.replay[n])
+    }
+
+    // The predicates below are all false once the input is exhausted.
+
+    fn is_whitespace(&self) -> bool {
+        if self.ascii_whitespace {
+            return matches!(
+                self.current_chr,
+                Some(' ') | Some('\t') | Some('\n') | Some('\r') | Some(FORM_FEED) | Some(VERTICAL_TAB)
+            );
+        }
+
+        self.current_chr.is_some_and(|chr| chr.is_whitespace())
+    }
+
+    fn is_newline(&mut self) -> bool {
+        match self.current_chr {
+            Some('\n') => true,
+            // Either a "\r\n" pair or a lone (old Mac style) carriage return
+            Some('\r') => {
+                if self.peek_char() == Some('\n') {
+                    self.next_char();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_alphanumeric(&self) -> bool {
+        self.current_chr.is_some_and(|chr| chr.is_ascii_alphanumeric())
+    }
+
+    fn is_letter(&self) -> bool {
+        self.current_chr.is_some_and(|chr| chr.is_ascii_alphabetic())
+    }
+
+    fn is_beginning_of_string(&self) -> bool {
+        self.char_equals(DOUBLE_QUOTES)
+    }
+
+    fn is_beginning_of_char(&self) -> bool {
+        self.char_equals(SINGLE_QUOTES)
+    }
+
+    fn is_digit(&self) -> bool {
+        self.current_chr.is_some_and(|chr| chr.is_ascii_digit())
+    }
+
+    fn is_operator(&self) -> bool {
+        self.current_chr.is_some_and(is_operator_char)
+    }
+
+    fn is_delimiter(&self) -> bool {
+        self.current_chr.is_some_and(is_delimiter_char)
+    }
+
+    fn char_equals(&self, compared_char: char) -> bool {
+        self.current_chr == Some(compared_char)
+    }
+
+    // A shebang (e.g: "#!/usr/bin/env bead") is only allowed at the very start of the source.
+    fn is_beginning_of_shebang(&mut self) -> bool {
+        self.row == 0
+            && self.column == 0
+            && self.current_chr == Some(HASH)
+            && self.peek_char() == Some('!')
+    }
+
+    // Skips up to (but not including) the next newline.
+    fn skip_line(&mut self) {
+        while self.current_chr.is_some() && !self.is_newline() {
+            self.next_char();
+        }
+    }
+
+    // "#[" opens an attribute (i.e: #[inline]) rather than a comment.
+    fn is_beginning_of_comment(&mut self) -> bool {
+        self.char_equals(HASH) && self.peek_char() != Some('[')
+    }
+
+    fn is_line_continuation(&mut self) -> bool {
+        self.char_equals(BACKSLASH) && matches!(self.peek_char(), Some('\n') | Some('\r'))
+    }
+
+    // Skips what may separate two adjacent strings. Unlike `skip_redundant_characters` it leaves
+    // statements and layout alone, so it stops at newlines wherever those are significant.
+    fn skip_to_adjacent_string(&mut self) {
+        let newlines_matter =
+            self.layout_mode || self.statement_terminator != StatementTerminator::Semicolon;
+
+        while self.current_chr.is_some() {
+            if self.is_beginning_of_comment() {
+                self.skip_comment();
+            } else if self.is_line_continuation() {
+                self.skip_line_continuation();
+            } else if self.is_newline() {
+                if newlines_matter {
+                    break;
+                }
+
+                self.next_char();
+                self.row += 1;
+                self.column = 0;
+            } else if self.is_whitespace() {
+                self.next_char();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn skip_line_continuation(&mut self) {
+        self.next_char();
+        self.is_newline();
+        self.next_char();
+        self.row += 1;
+        self.column = 0;
+    }
+
+    fn skip_redundant_characters(&mut self) {
+        // Whether nothing but whitespace was seen so far on the line being skipped
+        let mut blank_line = self.column == 0;
+        let mut blank_lines = 0;
+
+        while self.current_chr.is_some()
+            && (self.is_whitespace()
+                || self.is_newline()
+                || self.is_beginning_of_comment()
+                || self.is_line_continuation())
+        {
+            // Comments run until the end of the line (e.g: "int i = 5; # some comment")
+            if self.is_beginning_of_comment() {
+                self.record_blank_lines(&mut blank_lines);
+                self.skip_comment();
+                blank_line = false;
+            }
+            // A backslash right before a newline joins the two lines, so the next one doesn't start
+            // a new logical line.
+            else if self.is_line_continuation() {
+                self.skip_line_continuation();
+                blank_line = false;
+            } else if self.is_newline() {
+                if blank_line {
+                    blank_lines += 1;
+                }
+                blank_line = true;
+
+                if self.statement_terminator != StatementTerminator::Semicolon
+                    && self.statement_open
+                    && self.bracket_depth == 0
+                {
+                    self.statement_open = false;
+                    let terminator = self.zero_width_token(Token::Semicolon);
+                    self.pending_tokens.push_back(terminator);
+                }
+
+                self.next_char();
+                self.row += 1;
+                self.column = 0;
+                self.at_line_start = true;
+                self.indentation.clear();
+            } else {
+                let chr = self.current_chr.unwrap();
+
+                // Nor do they count as indentation
+                if self.layout_mode && self.at_line_start && chr != FORM_FEED && chr != VERTICAL_TAB {
+                    self.indentation.push(chr);
+                }
+                self.next_char();
+            }
+        }
+
+        self.record_blank_lines(&mut blank_lines);
+    }
+
+    // Skips a comment up to (but not including) the end of its line.
+    fn skip_comment(&mut self) {
+        let mut comment = String::from("");
+
+        self.next_char();
+
+        while self.current_chr.is_some() && !self.is_newline() {
+            comment.push(self.current_chr.unwrap());
+            self.next_char();
+        }
+
+        if self.collect_trivia {
+            self.trivia.push(Trivia::Comment(comment));
+        }
+    }
+
+    fn record_blank_lines(&mut self, blank_lines: &mut usize) {
+        if self.collect_trivia && *blank_lines > 0 {
+            self.trivia.push(Trivia::BlankLines(*blank_lines));
+        }
+        *blank_lines = 0;
+    }
+
+    fn handle_identifier(&mut self) -> Result<Token, LexerError> {
+        // Literal bytes value (i.e: b"h\x04\x12")
+        if self.char_equals(BYTES_PREFIX) && self.peek_char() == Some(DOUBLE_QUOTES) {
+            return self.handle_bytes();
+        }
+
+        // Format strings (i.e: f"x={x + 1}")
+        if self.char_equals(FORMAT_PREFIX) && self.peek_char() == Some(DOUBLE_QUOTES) {
+            return self.handle_format_string();
+        }
+
+        // Raw identifiers are symbols even when spelled like a keyword (i.e: r#class). Without a
+        // letter right after it, the '#' starts a comment as usual.
+        if self.char_equals(RAW_PREFIX)
+            && self.peek_char() == Some(HASH)
+            && self.peek_nth(1).is_some_and(|chr| chr.is_ascii_alphabetic() || chr == UNDERSCORE)
+        {
+            self.next_char();
+            self.next_char();
+
+            return Ok(Token::Symbol { name: self.scan_word()? });
+        }
+
+        let identifier = self.scan_word()?;
+        let keyword = if self.case_insensitive_keywords {
+            identifier.to_lowercase()
+        } else {
+            identifier.clone()
         };
+
+        if identifier == "_" {
+            return Ok(Token::Underscore);
+        }
+
+        // Common identifiers (e.g: "if", "true", "int", "while", ...)
+        if self.identifiers.contains_key(&keyword) {
+            Ok(self.identifiers.get(&keyword).unwrap().clone())
+        }
+        // Symbol names
+        else {
+            Ok(Token::Symbol { name: identifier })
+        }
+    }
+
+    fn scan_word(&mut self) -> Result<String, LexerError> {
+        let mut identifier = String::from("");
+        let mut length = 0;
+
+        // Loop until end of word. Past the maximum length the rest of it is skipped, not kept.
+        while self.current_chr.is_some() && (self.is_alphanumeric() || self.char_equals(UNDERSCORE)) {
+            if self.max_identifier_length.is_none_or(|max| length < max) {
+                identifier.push(self.current_chr.unwrap());
+            }
+
+            length += 1;
+            self.next_char();
+        }
+
+        self.current_char_processed = false;
+
+        match self.max_identifier_length {
+            Some(max) if length > max => Err(self.error(&format!(
+                "Identifier is {} chars long, more than the maximum of {}",
+                length, max
+            ))),
+            _ => Ok(identifier),
+        }
+    }
+
+    // Bytes literals may only contain ASCII characters and escape sequences (i.e: b"ab\x00\xff").
+    fn handle_bytes(&mut self) -> Result<Token, LexerError> {
+        let mut bytes: Vec<u8> = Vec::new();
+
+        // Skip the prefix and the opening double-quotes
+        self.next_char();
+        self.next_char();
+
+        while self.current_chr.is_some() && !self.char_equals(DOUBLE_QUOTES) {
+            let chr = self.current_chr.unwrap();
+
+            if chr == BACKSLASH {
+                match self.scan_escape().map_err(|err| self.abandon_literal(err, DOUBLE_QUOTES, true))? {
+                    Escape::Byte(byte) => bytes.push(byte),
+                    Escape::Char(chr) => bytes.extend_from_slice(chr.encode_utf8(&mut [0; 4]).as_bytes()),
+                }
+            } else if chr.is_ascii() {
+                bytes.push(chr as u8);
+            } else {
+                let err = self.error("Bytes literals may only contain ASCII characters");
+                return Err(self.abandon_literal(err, DOUBLE_QUOTES, true));
+            }
+
+            self.next_char();
+        }
+
+        if self.current_chr != Some(DOUBLE_QUOTES) {
+            return Err(self.error("Failed to parse bytes value: missing double-quotes"));
+        }
+
+        Ok(Token::BytesValue {
+            value: bytes,
+        })
+    }
+
+    // Lexes the whole format string at once, queueing everything after its `FormatStringStart`.
+    // Each hole is lexed on its own, as an expression, and `{{`/`}}` stand for literal braces.
+    fn handle_format_string(&mut self) -> Result<Token, LexerError> {
+        self.next_char();
+
+        let start_end = Position {
+            row: self.row,
+            column: self.column + 1,
+        };
+        let mut tokens: Vec<SpannedToken> = Vec::new();
+        let mut chunk = String::from("");
+
+        self.next_char();
+        let mut chunk_start = self.current_position();
+        let mut chunk_raw_start = self.lexeme.as_ref().map_or(0, String::len);
+
+        loop {
+            let chr = match self.current_chr {
+                Some(DOUBLE_QUOTES) => break,
+                Some(chr) => chr,
+                None => {
+                    return Err(self.error("Failed to parse format string: missing double-quotes"))
+                }
+            };
+
+            if (chr == '{' || chr == '}') && self.peek_char() == Some(chr) {
+                chunk.push(chr);
+                self.next_char();
+                self.next_char();
+                continue;
+            }
+
+            if chr == '}' {
+                let err = self.error("Unmatched '}' in format string (use '}}' for a literal one)");
+                return Err(self.abandon_literal(err, DOUBLE_QUOTES, true));
+            }
+
+            if chr == '{' {
+                if !chunk.is_empty() {
+                    let value = std::mem::take(&mut chunk);
+                    tokens.push(self.format_string_chunk(value, chunk_start, chunk_raw_start));
+                }
+
+                self.format_hole(&mut tokens)
+                    .map_err(|err| self.abandon_literal(err, DOUBLE_QUOTES, true))?;
+                self.next_char();
+                chunk_start = self.current_position();
+                chunk_raw_start = self.lexeme.as_ref().map_or(0, String::len);
+                continue;
+            }
+
+            if chr == BACKSLASH {
+                let escape = self.scan_char_escape();
+                chunk.push(escape.map_err(|err| self.abandon_literal(err, DOUBLE_QUOTES, true))?);
+                self.next_char();
+                continue;
+            }
+
+            if chr == REPLACEMENT_CHAR && self.reject_replacement_chars {
+                let err = self.error(REPLACEMENT_CHAR_ERROR);
+                return Err(self.abandon_literal(err, DOUBLE_QUOTES, true));
+            }
+
+            let ends_line = chr == '\n' || (chr == '\r' && self.peek_char() != Some('\n'));
+
+            chunk.push(chr);
+            self.next_char();
+
+            if ends_line {
+                self.row += 1;
+                self.column = 0;
+            }
+        }
+
+        let end = self.current_position();
+
+        if !chunk.is_empty() {
+            tokens.push(self.format_string_chunk(chunk, chunk_start, chunk_raw_start));
+        }
+
+        let quote_end = Position {
+            row: end.row,
+            column: end.column + 1,
+        };
+        tokens.push(self.format_string_part(Token::FormatStringEnd, end, quote_end));
+        self.pending_tokens.extend(tokens);
+
+        // The start only covers the prefix and the opening quote, the rest is in the queued tokens
+        self.token_end_override = Some(start_end);
+        self.lexeme = self.lexeme.as_ref().map(|_| FORMAT_PREFIX.to_string());
+
+        Ok(Token::FormatStringStart)
+    }
+
+    // Lexes the hole starting at the current '{', leaving its closing '}' as the current char.
+    fn format_hole(&mut self, tokens: &mut Vec<SpannedToken>) -> Result<(), LexerError> {
+        let hole_start = self.current_position();
+        tokens.push(self.format_string_part(
+            Token::FormatHoleStart,
+            hole_start,
+            Position {
+                row: hole_start.row,
+                column: hole_start.column + 1,
+            },
+        ));
+
+        self.next_char();
+
+        let source_start = self.current_position();
+        let mut source = String::from("");
+        let mut depth = 0;
+        let mut in_string = false;
+
+        // Braces only close the hole outside of nested brackets and strings (i.e: f"{d["}"]}")
+        loop {
+            let chr = match self.current_chr {
+                Some(chr) => chr,
+                None => return Err(self.error("Failed to parse format string: unclosed '{'")),
+            };
+
+            if in_string {
+                if chr == BACKSLASH && self.peek_char().is_some() {
+                    source.push(chr);
+                    self.next_char();
+                    source.push(self.current_chr.unwrap());
+                    self.next_char();
+                    continue;
+                }
+
+                in_string = chr != DOUBLE_QUOTES;
+            } else {
+                match chr {
+                    DOUBLE_QUOTES => in_string = true,
+                    '{' | '(' | '[' => depth += 1,
+                    '}' if depth == 0 => break,
+                    '}' | ')' | ']' => depth -= 1,
+                    _ => {}
+                }
+            }
+
+            let ends_line = chr == '\n' || (chr == '\r' && self.peek_char() != Some('\n'));
+
+            source.push(chr);
+            self.next_char();
+
+            if ends_line {
+                self.row += 1;
+                self.column = 0;
+            }
+        }
+
+        // Holes are lexed with the same rules, but as a lone expression: without layout, inserted
+        // semicolons or tracing (their tokens are traced once handed out by this lexer)
+        let mut hole = Lexer::new(source.chars());
+        hole.tab_width = self.tab_width;
+        hole.preserve_raw_lexemes = self.preserve_raw_lexemes;
+        hole.concat_adjacent_strings = self.concat_adjacent_strings;
+        hole.collect_trivia = self.collect_trivia;
+        hole.case_insensitive_keywords = self.case_insensitive_keywords;
+        hole.ascii_whitespace = self.ascii_whitespace;
+        hole.reject_replacement_chars = self.reject_replacement_chars;
+        hole.max_identifier_length = self.max_identifier_length;
+        let mut is_empty = true;
+
+        while let Some(result) = hole.try_next_spanned() {
+            let mut spanned = result.map_err(|mut err| {
+                err.span = offset_span(err.span, source_start);
+                err
+            })?;
+
+            spanned.span = offset_span(spanned.span, source_start);
+            tokens.push(spanned);
+            is_empty = false;
+        }
+
+        for mut warning in hole.warnings {
+            warning.span = offset_span(warning.span, source_start);
+            self.warnings.push(warning);
+        }
+
+        if is_empty {
+            return Err(self.error("Empty expression in format string"));
+        }
+
+        let hole_end = self.current_position();
+        tokens.push(self.format_string_part(
+            Token::FormatHoleEnd,
+            hole_end,
+            Position {
+                row: hole_end.row,
+                column: hole_end.column + 1,
+            },
+        ));
+
+        Ok(())
+    }
+
+    fn format_string_part(&self, token: Token, start: Position, end: Position) -> SpannedToken {
+        SpannedToken {
+            token,
+            span: Span { start, end },
+            raw: None,
+            leading_trivia: Vec::new(),
+        }
+    }
+
+    // A chunk ending at the current char, whose source starts at `raw_start` in the lexeme. Its
+    // raw text is kept either way, as it's written without quotes (and with its braces doubled).
+    fn format_string_chunk(&self, value: String, start: Position, raw_start: usize) -> SpannedToken {
+        let raw = match self.lexeme.as_ref() {
+            Some(lexeme) => String::from(&lexeme[raw_start..]),
+            None => value
+                .chars()
+                .map(|chr| match chr {
+                    '{' => String::from("{{"),
+                    '}' => String::from("}}"),
+                    _ => escape(chr, DOUBLE_QUOTES),
+                })
+                .collect(),
+        };
+
+        let mut chunk = self.format_string_part(Token::StringValue { value }, start, self.current_position());
+        chunk.raw = Some(raw);
+        chunk
+    }
+
+    // Reads the escape sequence starting at the current backslash, leaving its last character as
+    // the current one.
+    fn scan_escape(&mut self) -> Result<Escape, LexerError> {
+        self.next_char();
+
+        let escape = match self.current_chr {
+            Some('n') => Escape::Char('\n'),
+            Some('r') => Escape::Char('\r'),
+            Some('t') => Escape::Char('\t'),
+            Some('0') => Escape::Char('\0'),
+            Some(BACKSLASH) => Escape::Char(BACKSLASH),
+            Some(DOUBLE_QUOTES) => Escape::Char(DOUBLE_QUOTES),
+            Some(SINGLE_QUOTES) => Escape::Char(SINGLE_QUOTES),
+            // Exactly two hex digits (i.e: \x0a)
+            Some('x') => {
+                let mut digits = String::from("");
+
+                while digits.len() < 2 {
+                    match self.peek_char() {
+                        Some(chr) if chr.is_ascii_hexdigit() => {
+                            digits.push(chr);
+                            self.next_char();
+                        }
+                        _ => return Err(self.error("Invalid escape sequence: \\x must be followed by two hex digits")),
+                    }
+                }
+
+                Escape::Byte(u8::from_str_radix(&digits, 16).unwrap())
+            }
+            // Up to six hex digits between curly brackets (i.e: \u{1F600})
+            Some('u') => {
+                let mut digits = String::from("");
+
+                if self.peek_char() != Some('{') {
+                    return Err(self.error("Invalid escape sequence: \\u must be followed by '{'"));
+                }
+                self.next_char();
+
+                loop {
+                    match self.peek_char() {
+                        Some('}') if !digits.is_empty() => break,
+                        Some(chr) if chr.is_ascii_hexdigit() && digits.len() < 6 => {
+                            digits.push(chr);
+                            self.next_char();
+                        }
+                        _ => return Err(self.error("Invalid escape sequence: malformed unicode escape")),
+                    }
+                }
+                self.next_char();
+
+                match std::char::from_u32(u32::from_str_radix(&digits, 16).unwrap()) {
+                    Some(chr) => Escape::Char(chr),
+                    None => return Err(self.error("Invalid escape sequence: not a unicode scalar value")),
+                }
+            }
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+
+        Ok(escape)
+    }
+
+    // Escapes outside of bytes literals have to stand for a char, so \x is limited to ASCII.
+    fn scan_char_escape(&mut self) -> Result<char, LexerError> {
+        match self.scan_escape()? {
+            Escape::Char(chr) => Ok(chr),
+            Escape::Byte(byte) if byte.is_ascii() => Ok(byte as char),
+            Escape::Byte(_) => {
+                Err(self.error("Invalid escape sequence: \\x escapes must be ASCII outside of bytes"))
+            }
+        }
+    }
+
+    fn handle_number(&mut self) -> Result<Token, LexerError> {
+        if self.char_equals('0') {
+            match self.peek_char() {
+                Some('x') | Some('X') => return self.handle_radix_number(16),
+                Some('o') | Some('O') => return self.handle_radix_number(8),
+                Some('b') | Some('B') => return self.handle_radix_number(2),
+                _ => {}
+            }
+        }
+
+        let mut number = String::from("");
+        let mut is_float = false;
+
+        // A dot only belongs to the number if it's the first one and a digit follows it, so that
+        // "1.5.foo" is a member access on 1.5 (and "1.2.3" lexes as 1.2, '.', 3). This also leaves
+        // ranges alone, since a dot followed by another dot never starts a fraction.
+        while self.current_chr.is_some() {
+            if self.char_equals(DOT_SEPERATOR) {
+                match self.peek_char() {
+                    Some(chr) if !is_float && chr.is_ascii_digit() => is_float = true,
+                    _ => break,
+                }
+            } else if self.char_equals(UNDERSCORE) {
+                let follows_digit = number.ends_with(|chr: char| chr.is_ascii_digit());
+                self.skip_digit_separator(10, follows_digit)?;
+                continue;
+            } else if !self.is_digit() {
+                break;
+            }
+
+            number.push(self.current_chr.unwrap());
+            self.next_char();
+        }
+
+        // A `d` suffix asks for an exact decimal (i.e: 1.50d), unless it starts a longer word
+        let is_decimal = self.char_equals(DECIMAL_SUFFIX)
+            && !self.peek_char().is_some_and(|chr| chr.is_ascii_alphanumeric() || chr == UNDERSCORE);
+
+        if !is_decimal {
+            self.current_char_processed = false;
+        }
+
+        if is_float {
+            if number.starts_with('0') && number.chars().nth(1).is_some_and(|chr| chr.is_ascii_digit()) {
+                self.warn("Redundant leading zero in float");
+            }
+        } else if number.len() > 1 && number.starts_with('0') {
+            // Rather than silently reading C-style octals (i.e: 0755) as decimals, reject them
+            return Err(self.error("Leading zeros are not allowed in integers (use 0o for octal)"));
+        }
+
+        if is_decimal {
+            return Ok(Token::DecimalValue { value: number });
+        }
+
+        if is_float {
+            let parsed_number = number.parse::<f64>();
+
+            if parsed_number.is_err() {
+                return Err(self.error("Could not parse float"));
+            }
+
+            // Out of range literals would parse as inf, which doesn't round-trip through to_source
+            let value = parsed_number.unwrap();
+            if !value.is_finite() {
+                return Err(self.error("Float literal is too large"));
+            }
+
+            Ok(Token::FloatValue { value })
+        } else {
+            let parsed_number = BigInt::from_str(&number);
+
+            if parsed_number.is_err() {
+                return Err(self.error("Could not parse int"));
+            }
+
+            Ok(Token::IntValue {
+                value: parsed_number.unwrap(),
+            })
+        }
+    }
+
+    // Integers written with a radix prefix (i.e: 0x1F)
+    fn handle_radix_number(&mut self, radix: u32) -> Result<Token, LexerError> {
+        let mut digits = String::from("");
+
+        // Skip the prefix
+        self.next_char();
+        self.next_char();
+
+        loop {
+            match self.current_chr {
+                Some(chr) if chr.is_digit(radix) => {
+                    digits.push(chr);
+                    self.next_char();
+                }
+                Some(UNDERSCORE) => self.skip_digit_separator(radix, !digits.is_empty())?,
+                _ => break,
+            }
+        }
+
+        // There are no radix floats, so neither 0xF.5 nor C's 0x1p4 (or 0b1e3) quietly lex as an
+        // int followed by something else
+        let next = self.peek_char();
+        let fraction = self.char_equals(DOT_SEPERATOR) && next.is_some_and(|chr| chr.is_ascii_digit());
+        let exponent = (matches!(self.current_chr, Some('p') | Some('P'))
+            || (radix != 16 && matches!(self.current_chr, Some('e') | Some('E'))))
+            && next.is_some_and(|chr| chr.is_ascii_digit() || chr == '+' || chr == '-');
+
+        if !digits.is_empty() && (fraction || exponent) {
+            let name = match radix {
+                16 => "Hex",
+                8 => "Octal",
+                _ => "Binary",
+            };
+            return Err(self.error(&format!("{} literals may not contain '.' or exponent", name)));
+        }
+
+        self.current_char_processed = false;
+
+        match BigInt::parse_bytes(digits.as_bytes(), radix) {
+            Some(value) => Ok(Token::IntValue { value }),
+            None => Err(self.error("Could not parse int: missing digits after radix prefix")),
+        }
+    }
+
+    // Underscores may group digits (i.e: 1_000_000), but only ever between two of them.
+    fn skip_digit_separator(&mut self, radix: u32, follows_digit: bool) -> Result<(), LexerError> {
+        if !follows_digit || !self.peek_char().is_some_and(|chr| chr.is_digit(radix)) {
+            return Err(self.error("Underscores in numbers may only appear between digits"));
+        }
+
+        self.next_char();
+        Ok(())
+    }
+
+    fn handle_string(&mut self) -> Result<Token, LexerError> {
+        let mut string = String::from("");
+
+        self.scan_string(&mut string)?;
+
+        // "foo" "bar" lexes as a single "foobar" when adjacent strings are concatenated
+        while self.concat_adjacent_strings {
+            let checkpoint = self.checkpoint();
+
+            self.next_char();
+            self.skip_to_adjacent_string();
+
+            if self.current_chr == Some(DOUBLE_QUOTES) {
+                self.scan_string(&mut string)?;
+                continue;
+            }
+
+            // Whatever was skipped while looking for another string is lexed again, as usual
+            self.restore(checkpoint);
+            break;
+        }
+
+        Ok(Token::StringValue {
+            value: string
+        })
+    }
+
+    // Appends the body of the string literal starting at the current char, leaving the closing
+    // quote as the current char.
+    fn scan_string(&mut self, string: &mut String) -> Result<(), LexerError> {
+        self.next_char();
+
+        while let Some(chr) = self.current_chr.filter(|&chr| chr != DOUBLE_QUOTES) {
+            if chr == BACKSLASH {
+                // A backslash at the very end of a line joins the next one without a newline
+                if matches!(self.peek_char(), Some('\n') | Some('\r')) {
+                    self.next_char();
+                    self.is_newline();
+                    self.next_char();
+                    self.row += 1;
+                    self.column = 0;
+                } else {
+                    let escape = self.scan_char_escape();
+                    string.push(escape.map_err(|err| self.abandon_literal(err, DOUBLE_QUOTES, true))?);
+                    self.next_char();
+                }
+
+                continue;
+            }
+
+            if chr == REPLACEMENT_CHAR && self.reject_replacement_chars {
+                let err = self.error(REPLACEMENT_CHAR_ERROR);
+                return Err(self.abandon_literal(err, DOUBLE_QUOTES, true));
+            }
+
+            // Strings may span lines, so the position has to keep up with embedded newlines
+            // ("\r\n" counts once, on its '\n')
+            let ends_line = chr == '\n' || (chr == '\r' && self.peek_char() != Some('\n'));
+
+            string.push(chr);
+            self.next_char();
+
+            if ends_line {
+                self.row += 1;
+                self.column = 0;
+            }
+        }
+
+        if self.current_chr != Some(DOUBLE_QUOTES) {
+            return Err(self.error("Failed to parse string value: missing double-quotes"));
+        }
+
+        Ok(())
+    }
+
+    // Like strings, leaves the closing quote as the (processed) current char, so the next token
+    // starts right after it.
+    fn handle_char(&mut self) -> Result<Token, LexerError> {
+        self.next_char();
+
+        if self.current_chr.is_none() {
+            return Err(self.error("Failed to parse character value"));
+        }
+        else if self.current_chr.is_some() && self.char_equals(SINGLE_QUOTES) {
+            return Err(self.error("Character literal may only contain one codepoint"));
+        }
+
+        let chr = match self.current_chr.unwrap() {
+            BACKSLASH => {
+                let escape = self.scan_char_escape();
+                escape.map_err(|err| self.abandon_literal(err, SINGLE_QUOTES, false))?
+            }
+            REPLACEMENT_CHAR if self.reject_replacement_chars => {
+                let err = self.error(REPLACEMENT_CHAR_ERROR);
+                return Err(self.abandon_literal(err, SINGLE_QUOTES, false));
+            }
+            // These have to be escaped (i.e: '\n' rather than an actual line break)
+            '\n' | '\r' | '\t' => {
+                return Err(self.error("Character literal may not contain a raw newline or tab (use an escape)"));
+            }
+            chr => chr,
+        };
+
+        self.next_char();
+
+        if self.current_chr.is_none() || (self.current_chr.is_some() && !self.char_equals(SINGLE_QUOTES)) {
+            return Err(self.error("Failed to parse character value: missing single-quotes"));
+        }
+        
+        Ok(Token::CharValue {
+            value: chr
+        })
+    }
+
+    // Skips what's left of a literal after an error in its body, so lexing resumes right after its
+    // closing quote (i.e: "\q" x lexes x next). A literal without one ends with the input, or with
+    // its line unless it may span lines.
+    fn abandon_literal(&mut self, err: LexerError, quote: char, spans_lines: bool) -> LexerError {
+        while let Some(chr) = self.current_chr {
+            if chr == quote {
+                return err;
+            }
+
+            let ends_line = chr == '\n' || (chr == '\r' && self.peek_char() != Some('\n'));
+
+            if (chr == '\n' || chr == '\r') && !spans_lines {
+                self.current_char_processed = false;
+                return err;
+            }
+
+            // An escaped quote doesn't close the literal
+            if chr == BACKSLASH && matches!(self.peek_char(), Some(next) if next == quote || next == BACKSLASH) {
+                self.next_char();
+            }
+
+            self.next_char();
+
+            if ends_line {
+                self.row += 1;
+                self.column = 0;
+            }
+        }
+
+        err
+    }
+
+    // Signs are never part of a number literal: "-5" and "+5" are a (unary) operator followed by 5,
+    // and it's up to the parser to tell unary and binary uses apart.
+    fn handle_operator(&mut self) -> Result<Token, LexerError> {
+        match self.current_chr.unwrap() {
+            '+' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::AddAssign)
+                },
+                _ => Ok(Token::Add)
+            },
+            '-' => match self.peek_char() {
+                Some('>') => {
+                    self.next_char();
+                    Ok(Token::FnReturnTypeDelim)
+                },
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::SubtractAssign)
+                },
+                _ => Ok(Token::Subtract)
+            },
+            '*' => match (self.peek_char(), self.peek_nth(1)) {
+                (Some('*'), Some('=')) => {
+                    self.next_char();
+                    self.next_char();
+                    Ok(Token::PowAssign)
+                },
+                (Some('*'), _) => {
+                    self.next_char();
+                    Ok(Token::Power)
+                },
+                (Some('='), _) => {
+                    self.next_char();
+                    Ok(Token::MultiplyAssign)
+                },
+                _ => Ok(Token::Multiply)
+            },
+            '/' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::DivideAssign)
+                },
+                _ => Ok(Token::Divide)
+            },
+            '%' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::ModuloAssign)
+                },
+                _ => Ok(Token::Modulo)
+            },
+            '!' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::NotEquals)
+                },
+                _ => Ok(Token::Not)
+            },
+            '=' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::Equals)
+                },
+                Some('>') => {
+                    self.next_char();
+                    Ok(Token::FatArrow)
+                },
+                _ => Ok(Token::Assignment)
+            },
+            '|' => match self.peek_char() {
+                Some('|') => {
+                    self.next_char();
+                    Ok(Token::LogicalOr)
+                },
+                Some('>') => {
+                    self.next_char();
+                    Ok(Token::PipeForward)
+                },
+                _ => Ok(Token::BitwiseOr)
+            },
+            '&' => match self.peek_char() {
+                Some('&') => {
+                    self.next_char();
+                    Ok(Token::LogicalAnd)
+                },
+                _ => Ok(Token::BitwiseAnd)
+            },
+            '~' => Ok(Token::BitwiseNot),
+            '^' => Ok(Token::BitwiseXor),
+            '>' => match self.peek_char() {
+                Some('>') => {
+                    self.next_char();
+                    Ok(Token::BitwiseRightShift)
+                },
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::GreaterEqual)
+                },
+                _ => Ok(Token::Greater)
+            },
+            '<' => match self.peek_char() {
+                Some('<') => {
+                    self.next_char();
+                    Ok(Token::BitwiseLeftShift)
+                },
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::LessEqual)
+                },
+                _ => Ok(Token::Less)
+            },
+            '?' => match self.peek_char() {
+                Some('?') => {
+                    self.next_char();
+                    Ok(Token::NullCoalesce)
+                },
+                Some('.') => {
+                    self.next_char();
+                    Ok(Token::QuestionDot)
+                },
+                _ => Ok(Token::Question)
+            },
+            _ => Err(self.error("Could not parse operator"))
+        }
+    }
+
+    fn handle_delimiter(&mut self) -> Result<Token, LexerError> {
+        match self.current_chr.unwrap() {
+            '(' => Ok(Token::LeftParens),
+            ')' => Ok(Token::RightParens),
+            '{' => Ok(Token::LeftCurlyBracket),
+            '}' => Ok(Token::RightCurlyBracket),
+            '[' => Ok(Token::LeftSquareBracket),
+            ']' => Ok(Token::RightSquareBracket),
+            ';' => Ok(Token::Semicolon),
+            ',' => Ok(Token::Comma),
+            // A token of its own rather than part of a name, so "$x" (i.e: for interpolation) is up
+            // to the parser
+            '$' => Ok(Token::Dollar),
+            '.' => match (self.peek_char(), self.peek_nth(1)) {
+                (Some('.'), Some('.')) => {
+                    self.next_char();
+                    self.next_char();
+                    Ok(Token::Ellipsis)
+                },
+                (Some('.'), _) => {
+                    self.next_char();
+                    Ok(Token::RangeExclusive)
+                },
+                _ => Ok(Token::MemberAccessor)
+            },
+            // There's no turbofish token: "Foo::<int>" is '::' then '<', and whether angle brackets
+            // are generics or comparisons (or '>>' two closing ones) is up to the parser
+            ':' => match self.peek_char() {
+                Some(':') => {
+                    self.next_char();
+                    Ok(Token::StaticAccessor)
+                },
+                Some('=') => {
+                    self.next_char();
+                    Ok(Token::Walrus)
+                },
+                _ => Ok(Token::Colon)
+            },
+            _ => Err(self.error("Could not parse delimiter"))
+        }
+    }
+}
+
+impl<T> Iterator for Lexer<T>
+where
+    T: Iterator<Item = char>,
+{
+    type Item = Result<Token, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next()
+    }
+}
+
+impl<R> Lexer<ReaderChars<R>>
+where
+    R: BufRead,
+{
+    // Lexes straight out of a reader, without loading the whole source into memory first.
+    pub fn from_reader(reader: R) -> Self {
+        let chars = ReaderChars::new(reader);
+        let source_error = chars.error();
+        let mut lexer = Lexer::new(chars);
+
+        lexer.source_error = Some(source_error);
+        lexer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Lexer<std::str::Chars<'a>> {
+    // Reads the whole file and lexes it. Failing to read it is reported like any other error, with
+    // an empty span at the start of the file.
+    pub fn tokenize_file<P: AsRef<Path>>(path: P) -> Result<Vec<Token>, LexerError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|err| LexerError {
+            message: format!("Failed to read {}: {}", path.display(), err),
+            span: Span {
+                start: Position { row: 0, column: 0 },
+                end: Position { row: 0, column: 0 },
+            },
+        })?;
+
+        Lexer::new(source.chars()).tokenize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::errors::{LexerError, LexerWarning};
+    use crate::parser::lexer::{
+        bracket_balance, builtin_types, ends_with_terminator, is_delimiter_char, is_operator_char,
+        keywords, BracketBalance, Lexer, StatementTerminator, REPLACEMENT_CHAR_ERROR,
+    };
+    use crate::parser::span::{Position, Span, SpannedToken, Trivia};
+    use crate::parser::token::{Token, TokenKind};
+    use num_bigint::BigInt;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    // Collects tokens up to (and excluding) `Eof` or the first error.
+    pub fn lex_source(source: &str) -> Vec<Token> {
+        let mut lexer = Lexer::new(source.chars());
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut token: Result<Token, LexerError> = lexer.next_token();
+
+        while token.is_ok() && token.as_ref().unwrap() != &Token::Eof {
+            tokens.push(token.unwrap());
+            token = lexer.next_token();
+        }
+
+        tokens
+    }
+
+    #[test]
+    fn test_class_structure() {
+        let source = String::from(r#"
+            class Logger {
+                priv str name;
+
+                fn construct(str name) {
+                    self.name = name;
+                }
+                
+                fn destruct() {
+                    int i = 76;
+                }
+
+                fn clone() -> Logger;
+            }
+        "#);
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Class,
+                Token::symbol("Logger"),
+                Token::LeftCurlyBracket,
+                Token::Private,
+                Token::StringType,
+                Token::symbol("name"),
+                Token::Semicolon,
+                Token::Function,
+                Token::Constructor,
+                Token::LeftParens,
+                Token::StringType,
+                Token::symbol("name"),
+                Token::RightParens,
+                Token::LeftCurlyBracket,
+                Token::SelfInstance,
+                Token::MemberAccessor,
+                Token::symbol("name"),
+                Token::Assignment,
+                Token::symbol("name"),
+                Token::Semicolon,
+                Token::RightCurlyBracket,
+                Token::Function,
+                Token::Destructor,
+                Token::LeftParens,
+                Token::RightParens,
+                Token::LeftCurlyBracket,
+                Token::IntType,
+                Token::symbol("i"),
+                Token::Assignment,
+                Token::int(76),
+                Token::Semicolon,
+                Token::RightCurlyBracket,
+                Token::Function,
+                Token::symbol("clone"),
+                Token::LeftParens,
+                Token::RightParens,
+                Token::FnReturnTypeDelim,
+                Token::symbol("Logger"),
+                Token::Semicolon,
+                Token::RightCurlyBracket
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assignment_expressions() {
+        let source = String::from(r#"
+            int i = 5;
+            float f = 3.54;
+            bool b = true;
+            str s1 = "Hello World";
+            bytes bb = b"\x34b";
+            list l = [i, f, b, s1, "some_val"];
+        "#);
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::IntType,
+                Token::symbol("i"),
+                Token::Assignment,
+                Token::int(5),
+                Token::Semicolon,
+                Token::FloatType,
+                Token::symbol("f"),
+                Token::Assignment,
+                Token::float(3.54),
+                Token::Semicolon,
+                Token::BoolType,
+                Token::symbol("b"),
+                Token::Assignment,
+                Token::bool(true),
+                Token::Semicolon,
+                Token::StringType,
+                Token::symbol("s1"),
+                Token::Assignment,
+                Token::string("Hello World"),
+                Token::Semicolon,
+                Token::BytesType,
+                Token::symbol("bb"),
+                Token::Assignment,
+                Token::bytes(&[0x34, b'b']),
+                Token::Semicolon,
+                Token::ListType,
+                Token::symbol("l"),
+                Token::Assignment,
+                Token::LeftSquareBracket,
+                Token::symbol("i"),
+                Token::Comma,
+                Token::symbol("f"),
+                Token::Comma,
+                Token::symbol("b"),
+                Token::Comma,
+                Token::symbol("s1"),
+                Token::Comma,
+                Token::string("some_val"),
+                Token::RightSquareBracket,
+                Token::Semicolon
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variable_identifiers() {
+        let source =
+            String::from("str some_str int i float _ff bytes ba char c tuple t list lll dict d enum e");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::StringType,
+                Token::Symbol {
+                    name: String::from("some_str")
+                },
+                Token::IntType,
+                Token::Symbol {
+                    name: String::from("i")
+                },
+                Token::FloatType,
+                Token::Symbol {
+                    name: String::from("_ff")
+                },
+                Token::BytesType,
+                Token::Symbol {
+                    name: String::from("ba")
+                },
+                Token::CharType,
+                Token::Symbol {
+                    name: String::from("c")
+                },
+                Token::TupleType,
+                Token::Symbol {
+                    name: String::from("t")
+                },
+                Token::ListType,
+                Token::Symbol {
+                    name: String::from("lll")
+                },
+                Token::DictType,
+                Token::Symbol {
+                    name: String::from("d")
+                },
+                Token::EnumType,
+                Token::Symbol {
+                    name: String::from("e")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let source = String::from(r#""Some string value""#);
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![Token::StringValue {
+                value: String::from("Some string value")
+            },]
+        );
+    }
+
+    #[test]
+    fn test_bytes_literal() {
+        let source = String::from(r#"b"hello \x01\03 \x44""#);
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![Token::BytesValue {
+                value: vec![b'h', b'e', b'l', b'l', b'o', b' ', 0x01, 0x00, b'3', b' ', 0x44]
+            },]
+        );
+    }
+
+    #[test]
+    fn test_character_literal() {
+        let source = String::from("'a'");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![Token::CharValue {
+                value: 'a'
+            },]
+        );
+    }
+
+    #[test]
+    fn test_number_literal() {
+        let source = String::from("423 763.433 0 24454333");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::IntValue {
+                    value: BigInt::from(423)
+                },
+                Token::FloatValue { value: 763.433 },
+                Token::IntValue {
+                    value: BigInt::from(0)
+                },
+                Token::IntValue {
+                    value: BigInt::from(24454333)
+                },
+            ]
+        );
+
+        let source = format!("{}.5 x", "9".repeat(400));
+        let mut lexer = Lexer::new(source.chars());
+        assert_eq!(lexer.next_token().unwrap_err().message, "Float literal is too large");
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("x"));
+    }
+
+    #[test]
+    fn test_operators() {
+        let source = String::from("|| && + - * / % | ^ ~ & >> << ! == != > >= < <= =");
+        let stream = Lexer::new(source.chars()).lex_with_spans().unwrap();
+        assert_eq!(
+            stream.expect_sequence(&[
+                TokenKind::LogicalOr,
+                TokenKind::LogicalAnd,
+                TokenKind::Add,
+                TokenKind::Subtract,
+                TokenKind::Multiply,
+                TokenKind::Divide,
+                TokenKind::Modulo,
+                TokenKind::BitwiseOr,
+                TokenKind::BitwiseXor,
+                TokenKind::BitwiseNot,
+                TokenKind::BitwiseAnd,
+                TokenKind::BitwiseRightShift,
+                TokenKind::BitwiseLeftShift,
+                TokenKind::Not,
+                TokenKind::Equals,
+                TokenKind::NotEquals,
+                TokenKind::Greater,
+                TokenKind::GreaterEqual,
+                TokenKind::Less,
+                TokenKind::LessEqual,
+                TokenKind::Assignment
+            ]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_delimiters() {
+        let source = String::from("( ) { } [ ] . ; , :: : ->");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LeftParens,
+                Token::RightParens,
+                Token::LeftCurlyBracket,
+                Token::RightCurlyBracket,
+                Token::LeftSquareBracket,
+                Token::RightSquareBracket,
+                Token::MemberAccessor,
+                Token::Semicolon,
+                Token::Comma,
+                Token::StaticAccessor,
+                Token::Colon,
+                Token::FnReturnTypeDelim
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fn_return_type_delimiter() {
+        let source = String::from("fn f()->int a->b c - > d -5");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Function,
+                Token::Symbol { name: String::from("f") },
+                Token::LeftParens,
+                Token::RightParens,
+                Token::FnReturnTypeDelim,
+                Token::IntType,
+                Token::Symbol { name: String::from("a") },
+                Token::FnReturnTypeDelim,
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("c") },
+                Token::Subtract,
+                Token::Greater,
+                Token::Symbol { name: String::from("d") },
+                Token::Subtract,
+                Token::IntValue { value: BigInt::from(5) }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_colon() {
+        let source = String::from("a: int b :c::d");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Symbol { name: String::from("a") },
+                Token::Colon,
+                Token::IntType,
+                Token::Symbol { name: String::from("b") },
+                Token::Colon,
+                Token::Symbol { name: String::from("c") },
+                Token::StaticAccessor,
+                Token::Symbol { name: String::from("d") }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let source = Cursor::new(String::from("fn main() {\n    str s = \"héllo\";\n}").into_bytes());
+        let mut lexer = Lexer::from_reader(source);
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut token: Result<Token, LexerError> = lexer.next_token();
+
+        while token.is_ok() && token.as_ref().unwrap() != &Token::Eof {
+            tokens.push(token.unwrap());
+            token = lexer.next_token();
+        }
+
+        assert_eq!(token.unwrap(), Token::Eof);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Function,
+                Token::Symbol { name: String::from("main") },
+                Token::LeftParens,
+                Token::RightParens,
+                Token::LeftCurlyBracket,
+                Token::StringType,
+                Token::Symbol { name: String::from("s") },
+                Token::Assignment,
+                Token::StringValue { value: String::from("héllo") },
+                Token::Semicolon,
+                Token::RightCurlyBracket
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_reader_invalid_utf8() {
+        let source = Cursor::new(vec![b'i', b'n', b't', b' ', 0xff, b'x']);
+        let mut lexer = Lexer::from_reader(source);
+
+        assert_eq!(lexer.next_token().unwrap(), Token::IntType);
+
+        let error = lexer.next_token().unwrap_err();
+        assert_eq!(error.message, "Failed to decode source: invalid UTF-8");
+        assert_eq!(error.span.start, Position { row: 0, column: 4 });
+    }
+
+    #[test]
+    fn test_repeated_unary_operators() {
+        let source = String::from("!!x ~~y !=z !!=");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Not,
+                Token::Not,
+                Token::Symbol { name: String::from("x") },
+                Token::BitwiseNot,
+                Token::BitwiseNot,
+                Token::Symbol { name: String::from("y") },
+                Token::NotEquals,
+                Token::Symbol { name: String::from("z") },
+                Token::Not,
+                Token::NotEquals
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let source = String::from("int i\n  = 5;");
+        let mut lexer = Lexer::new(source.chars());
+
+        let int_type = lexer.next_spanned_token().unwrap();
+        assert_eq!(int_type.span.start, Position { row: 0, column: 0 });
+        assert_eq!(int_type.span.end, Position { row: 0, column: 3 });
+
+        let symbol = lexer.next_spanned_token().unwrap();
+        assert_eq!(symbol.span.start, Position { row: 0, column: 4 });
+        assert_eq!(symbol.span.end, Position { row: 0, column: 5 });
+
+        let assignment = lexer.next_spanned_token().unwrap();
+        assert_eq!(assignment.span.start, Position { row: 1, column: 2 });
+        assert_eq!(assignment.span.end, Position { row: 1, column: 3 });
+
+        let value = lexer.next_spanned_token().unwrap();
+        assert_eq!(value.span.start, Position { row: 1, column: 4 });
+    }
+
+    #[test]
+    fn test_tab_width() {
+        let source = String::from("\tfoo \t bar\n\t\tbaz");
+
+        let mut lexer = Lexer::new(source.chars());
+        assert_eq!(lexer.next_spanned_token().unwrap().span.start, Position { row: 0, column: 1 });
+        assert_eq!(lexer.next_spanned_token().unwrap().span.start, Position { row: 0, column: 7 });
+
+        let mut lexer = Lexer::new(source.chars()).with_tab_width(4);
+        let foo = lexer.next_spanned_token().unwrap();
+        assert_eq!(foo.token, Token::Symbol { name: String::from("foo") });
+        assert_eq!(foo.span.start, Position { row: 0, column: 4 });
+        assert_eq!(lexer.next_spanned_token().unwrap().span.start, Position { row: 0, column: 13 });
+        assert_eq!(lexer.next_spanned_token().unwrap().span.start, Position { row: 1, column: 8 });
+    }
+
+    #[test]
+    fn test_shebang() {
+        let source = String::from("#!/usr/bin/env bead\nint i;");
+        let mut lexer = Lexer::new(source.chars());
+
+        let int_type = lexer.next_spanned_token().unwrap();
+        assert_eq!(int_type.token, Token::IntType);
+        assert_eq!(int_type.span.start, Position { row: 1, column: 0 });
+        assert_eq!(
+            lex_source(&source),
+            vec![
+                Token::IntType,
+                Token::Symbol { name: String::from("i") },
+                Token::Semicolon
+            ]
+        );
+        assert_eq!(lex_source("#!/usr/bin/env bead"), vec![]);
+    }
+
+    #[test]
+    fn test_shebang_only_at_start() {
+        // Anywhere but the very start, "#!" is just a comment.
+        let source = String::from("int i;\n#!/usr/bin/env bead\ni");
+        let mut lexer = Lexer::new(source.chars());
+
+        for _ in 0..3 {
+            assert!(lexer.next_token().is_ok());
+        }
+
+        let symbol = lexer.next_spanned_token().unwrap();
+        assert_eq!(symbol.token, Token::Symbol { name: String::from("i") });
+        assert_eq!(symbol.span.start, Position { row: 2, column: 0 });
+    }
+
+    #[test]
+    fn test_hash_comments() {
+        let source = String::from("int i = 5; # some comment\n# another one\ni = 6;#");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::IntType,
+                Token::Symbol { name: String::from("i") },
+                Token::Assignment,
+                Token::IntValue { value: BigInt::from(5) },
+                Token::Semicolon,
+                Token::Symbol { name: String::from("i") },
+                Token::Assignment,
+                Token::IntValue { value: BigInt::from(6) },
+                Token::Semicolon
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash_inside_string() {
+        let source = String::from(r#"str s = "not # a comment"; # a comment"#);
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::StringType,
+                Token::Symbol { name: String::from("s") },
+                Token::Assignment,
+                Token::StringValue { value: String::from("not # a comment") },
+                Token::Semicolon
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hex_literal() {
+        let source = String::from("0x0A 0xff 0XdeadBEEF 0x");
+        let mut lexer = Lexer::new(source.chars());
+
+        assert_eq!(lexer.next_token().unwrap(), Token::IntValue { value: BigInt::from(10) });
+        assert_eq!(lexer.next_token().unwrap(), Token::IntValue { value: BigInt::from(255) });
+        assert_eq!(lexer.next_token().unwrap(), Token::IntValue { value: BigInt::from(0xdeadbeef_u32) });
+        assert!(lexer.next_token().unwrap_err().message.contains("missing digits"));
+    }
+
+    #[test]
+    fn test_raw_lexemes() {
+        let source = String::from("0x0A 7.50 \"a b\" b\"\\x01\"; foo");
+        let mut lexer = Lexer::new(source.chars()).with_raw_lexemes(true);
+        let mut raw: Vec<String> = Vec::new();
+        let mut tokens: Vec<Token> = Vec::new();
+
+        while let Some(Ok(spanned)) = lexer.try_next_spanned() {
+            raw.push(spanned.raw.unwrap());
+            tokens.push(spanned.token);
+        }
+
+        assert_eq!(raw, vec!["0x0A", "7.50", "\"a b\"", "b\"\\x01\"", ";", "foo"]);
+        assert_eq!(tokens[0], Token::IntValue { value: BigInt::from(10) });
+        assert_eq!(tokens[1], Token::FloatValue { value: 7.5 });
+
+        let mut lexer = Lexer::new(source.chars());
+        assert_eq!(lexer.next_spanned_token().unwrap().raw, None);
+    }
+
+    #[test]
+    fn test_try_next() {
+        let source = String::from("x = 1;");
+        let mut lexer = Lexer::new(source.chars());
+
+        assert_eq!(lexer.try_next().unwrap().unwrap(), Token::Symbol { name: String::from("x") });
+        assert_eq!(lexer.try_next().unwrap().unwrap(), Token::Assignment);
+        assert_eq!(lexer.try_next().unwrap().unwrap(), Token::IntValue { value: BigInt::from(1) });
+        assert_eq!(lexer.try_next().unwrap().unwrap(), Token::Semicolon);
+        assert!(lexer.try_next().is_none());
+        assert!(lexer.try_next().is_none());
+
+        let source = String::from("x `");
+        let mut lexer = Lexer::new(source.chars());
+
+        assert!(lexer.try_next().unwrap().is_ok());
+        assert!(lexer.try_next().unwrap().is_err());
+        assert!(lexer.try_next().is_none());
+    }
+
+    #[test]
+    fn test_iterator() {
+        let source = String::from("fn f() -> int;");
+        let tokens: Result<Vec<Token>, LexerError> = Lexer::new(source.chars()).collect();
+        assert_eq!(
+            tokens.unwrap(),
+            vec![
+                Token::Function,
+                Token::Symbol { name: String::from("f") },
+                Token::LeftParens,
+                Token::RightParens,
+                Token::FnReturnTypeDelim,
+                Token::IntType,
+                Token::Semicolon
+            ]
+        );
+    }
+
+    #[test]
+    fn test_number_followed_by_dot() {
+        let source = String::from("1.5.foo 1.2.3 7.bar");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::FloatValue { value: 1.5 },
+                Token::MemberAccessor,
+                Token::Symbol { name: String::from("foo") },
+                Token::FloatValue { value: 1.2 },
+                Token::MemberAccessor,
+                Token::IntValue { value: BigInt::from(3) },
+                Token::IntValue { value: BigInt::from(7) },
+                Token::MemberAccessor,
+                Token::Symbol { name: String::from("bar") }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ampersand() {
+        let source = String::from("&x x & y x && y x&y x&&y &&&");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::BitwiseAnd,
+                Token::Symbol { name: String::from("x") },
+                Token::Symbol { name: String::from("x") },
+                Token::BitwiseAnd,
+                Token::Symbol { name: String::from("y") },
+                Token::Symbol { name: String::from("x") },
+                Token::LogicalAnd,
+                Token::Symbol { name: String::from("y") },
+                Token::Symbol { name: String::from("x") },
+                Token::BitwiseAnd,
+                Token::Symbol { name: String::from("y") },
+                Token::Symbol { name: String::from("x") },
+                Token::LogicalAnd,
+                Token::Symbol { name: String::from("y") },
+                Token::LogicalAnd,
+                Token::BitwiseAnd
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layout_mode() {
+        let source = String::from("if a\n    if b\n        x\n\n    # comment\n    y\nz\nif c\n  w");
+        let tokens: Vec<Token> = Lexer::new(source.chars())
+            .with_layout_mode(true)
+            .map(|token| token.unwrap())
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::If,
+                Token::Symbol { name: String::from("a") },
+                Token::Indent,
+                Token::If,
+                Token::Symbol { name: String::from("b") },
+                Token::Indent,
+                Token::Symbol { name: String::from("x") },
+                Token::Dedent,
+                Token::Symbol { name: String::from("y") },
+                Token::Dedent,
+                Token::Symbol { name: String::from("z") },
+                Token::If,
+                Token::Symbol { name: String::from("c") },
+                Token::Indent,
+                Token::Symbol { name: String::from("w") },
+                Token::Dedent
+            ]
+        );
+
+        let tokens = lex_source(&source);
+        assert!(!tokens.contains(&Token::Indent));
+        assert!(!tokens.contains(&Token::Dedent));
+    }
+
+    #[test]
+    fn test_layout_mode_inside_brackets() {
+        let source = String::from("f(a,\n    b)\nc");
+        let tokens: Vec<Token> = Lexer::new(source.chars())
+            .with_layout_mode(true)
+            .map(|token| token.unwrap())
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::symbol("f"),
+                Token::LeftParens,
+                Token::symbol("a"),
+                Token::Comma,
+                Token::symbol("b"),
+                Token::RightParens,
+                Token::symbol("c")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layout_mode_inconsistent_dedent() {
+        let source = String::from("if a\n    x\n  yy");
+        let mut lexer = Lexer::new(source.chars()).with_layout_mode(true);
+
+        for _ in 0..4 {
+            assert!(lexer.next_token().is_ok());
+        }
+        assert_eq!(
+            lexer.next_token().unwrap_err().message,
+            "Dedent does not match any outer indentation level"
+        );
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("yy"));
+    }
+
+    #[test]
+    fn test_assert_and_yield() {
+        let source = String::from("assert x == 1; yield value;");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Assert,
+                Token::Symbol { name: String::from("x") },
+                Token::Equals,
+                Token::IntValue { value: BigInt::from(1) },
+                Token::Semicolon,
+                Token::Yield,
+                Token::Symbol { name: String::from("value") },
+                Token::Semicolon
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let source = String::from("int i;\nstr s = \"abc de");
+        let mut lexer = Lexer::new(source.chars());
+
+        for _ in 0..6 {
+            assert!(lexer.next_token().is_ok());
+        }
+
+        let error = lexer.next_token().unwrap_err();
+        assert_eq!(error.message, "Failed to parse string value: missing double-quotes");
+        assert_eq!(
+            error.span,
+            Span {
+                start: Position { row: 1, column: 8 },
+                end: Position { row: 1, column: 15 },
+            }
+        );
+
+        let source = String::from("x = §y§;");
+        let mut lexer = Lexer::new(source.chars());
+
+        for _ in 0..2 {
+            assert!(lexer.next_token().is_ok());
+        }
+
+        let error = lexer.next_token().unwrap_err();
+        assert_eq!(error.message, "Failed to lex source");
+        assert_eq!(
+            error.span,
+            Span {
+                start: Position { row: 0, column: 4 },
+                end: Position { row: 0, column: 5 },
+            }
+        );
+
+    }
+
+    #[test]
+    fn test_walrus() {
+        let source = String::from("a := b a: b a::b a:=:b ::=");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Symbol { name: String::from("a") },
+                Token::Walrus,
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("a") },
+                Token::Colon,
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("a") },
+                Token::StaticAccessor,
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("a") },
+                Token::Walrus,
+                Token::Colon,
+                Token::Symbol { name: String::from("b") },
+                Token::StaticAccessor,
+                Token::Assignment
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expect() {
+        let source = String::from("fn foo(");
+        let mut lexer = Lexer::new(source.chars());
+
+        assert!(lexer.expect(&Token::Function).is_ok());
+        assert_eq!(
+            lexer.expect_kind(TokenKind::Symbol).unwrap(),
+            Token::Symbol { name: String::from("foo") }
+        );
+
+        let error = lexer.expect(&Token::LeftCurlyBracket).unwrap_err();
+        assert_eq!(error.message, "Expected LeftCurlyBracket, found LeftParens");
+        assert_eq!(error.span.start, Position { row: 0, column: 6 });
+
+        assert_eq!(lexer.expect(&Token::Semicolon).unwrap_err().message, "Expected Semicolon, found Eof");
+    }
+
+    #[test]
+    fn test_expect_mismatch() {
+        let source = String::from("bar 5");
+        let mut lexer = Lexer::new(source.chars());
+
+        let error = lexer.expect(&Token::Symbol { name: String::from("foo") }).unwrap_err();
+        assert_eq!(
+            error.message,
+            r#"Expected Symbol { name: "foo" }, found Symbol { name: "bar" }"#
+        );
+
+        let error = lexer.expect_kind(TokenKind::StringValue).unwrap_err();
+        assert!(error.message.starts_with("Expected StringValue, found IntValue"));
+        assert_eq!(error.span.start, Position { row: 0, column: 4 });
+    }
+
+    #[test]
+    fn test_empty_source() {
+        for source in &["", "   \n  ", "\n\n", "  # just a comment\n\t# and another\n"] {
+            let mut lexer = Lexer::new(source.chars());
+
+            let eof = lexer.next_spanned_token().unwrap();
+            assert_eq!(eof.token, Token::Eof);
+            assert_eq!(eof.span.start, eof.span.end);
+            assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+
+            assert!(Lexer::new(source.chars()).next().is_none());
+            assert!(Lexer::new(source.chars()).with_layout_mode(true).next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_eof_is_repeated() {
+        let mut lexer = Lexer::new("x".chars());
+
+        assert_eq!(lexer.next_token().unwrap(), Token::Symbol { name: String::from("x") });
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+        assert!(lexer.try_next().is_none());
+    }
+
+    #[test]
+    fn test_adjacent_strings() {
+        let source = String::from("\"foo\" \"bar\"\n  # comment\n \"baz\"; \"qux\"");
+        assert_eq!(
+            lex_source(&source),
+            vec![
+                Token::StringValue { value: String::from("foo") },
+                Token::StringValue { value: String::from("bar") },
+                Token::StringValue { value: String::from("baz") },
+                Token::Semicolon,
+                Token::StringValue { value: String::from("qux") }
+            ]
+        );
+
+        let mut lexer = Lexer::new(source.chars())
+            .with_adjacent_string_concatenation(true)
+            .with_raw_lexemes(true);
+
+        let merged = lexer.next_spanned_token().unwrap();
+        assert_eq!(merged.token, Token::StringValue { value: String::from("foobarbaz") });
+        assert_eq!(merged.span.start, Position { row: 0, column: 0 });
+        assert_eq!(merged.span.end, Position { row: 2, column: 6 });
+
+        let semicolon = lexer.next_spanned_token().unwrap();
+        assert_eq!(semicolon.token, Token::Semicolon);
+        assert_eq!(semicolon.span.start, Position { row: 2, column: 6 });
+
+        let last = lexer.next_spanned_token().unwrap();
+        assert_eq!(last.token, Token::StringValue { value: String::from("qux") });
+        assert_eq!(last.span.end, Position { row: 2, column: 13 });
+        assert_eq!(last.raw.unwrap(), "\"qux\"");
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_adjacent_string_then_token() {
+        let source = String::from("\"foo\"   x");
+        let mut lexer = Lexer::new(source.chars())
+            .with_adjacent_string_concatenation(true)
+            .with_raw_lexemes(true);
+
+        let string = lexer.next_spanned_token().unwrap();
+        assert_eq!(string.token, Token::StringValue { value: String::from("foo") });
+        assert_eq!(string.span.end, Position { row: 0, column: 5 });
+        assert_eq!(string.raw.unwrap(), "\"foo\"");
+
+        let symbol = lexer.next_spanned_token().unwrap();
+        assert_eq!(symbol.token, Token::Symbol { name: String::from("x") });
+        assert_eq!(symbol.span.start, Position { row: 0, column: 8 });
+    }
+
+    #[test]
+    fn test_literals_after_bytes_rework() {
+        let source = String::from(r#"b"x1" b "x2" ab"x3" b bb 'b' "b" 12 1.5 true null b"""#);
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::BytesValue { value: b"x1".to_vec() },
+                Token::Symbol { name: String::from("b") },
+                Token::StringValue { value: String::from("x2") },
+                Token::Symbol { name: String::from("ab") },
+                Token::StringValue { value: String::from("x3") },
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("bb") },
+                Token::CharValue { value: 'b' },
+                Token::StringValue { value: String::from("b") },
+                Token::IntValue { value: BigInt::from(12) },
+                Token::FloatValue { value: 1.5 },
+                Token::BoolValue { value: true },
+                Token::NullValue,
+                Token::BytesValue { value: vec![] }
+            ]
+        );
+
+        let mut lexer = Lexer::new(r#"b"abc"#.chars());
+        assert_eq!(
+            lexer.next_token().unwrap_err().message,
+            "Failed to parse bytes value: missing double-quotes"
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let source = String::from("a % b a %= b a+=1 a -= 1 a*=b a /=b a%b%=c");
+        let tokens = lex_source(&source);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Symbol { name: String::from("a") },
+                Token::Modulo,
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("a") },
+                Token::ModuloAssign,
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("a") },
+                Token::AddAssign,
+                Token::IntValue { value: BigInt::from(1) },
+                Token::Symbol { name: String::from("a") },
+                Token::SubtractAssign,
+                Token::IntValue { value: BigInt::from(1) },
+                Token::Symbol { name: String::from("a") },
+                Token::MultiplyAssign,
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("a") },
+                Token::DivideAssign,
+                Token::Symbol { name: String::from("b") },
+                Token::Symbol { name: String::from("a") },
+                Token::Modulo,
+                Token::Symbol { name: String::from("b") },
+                Token::ModuloAssign,
+                Token::Symbol { name: String::from("c") }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_modulo_at_end_of_input() {
+        let mut lexer = Lexer::new("a %".chars());
+
+        assert_eq!(lexer.next_token().unwrap(), Token::Symbol { name: String::from("a") });
+        assert_eq!(lexer.next_token().unwrap(), Token::Modulo);
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_no_panic_at_end_of_input() {
+        let sources = [
+            "x", "x.", "1", "1.", "0x", "'", "'a", "\"", "\"abc", "b", "b\"", "b\"a", ":", "-", "!",
+            "=", "|", "&", ">", "<", " ", "\r", "#", "#!",
+        ];
+
+        for source in &sources {
+            let mut lexer = Lexer::new(source.chars());
+
+            for _ in 0..3 {
+                let _ = lexer.next_token();
+            }
+            assert!(lexer.try_next().is_none(), "{:?} did not reach the end of input", source);
+        }
+    }
+
+    #[test]
+    fn test_leading_zeros() {
+        let mut lexer = Lexer::new("0 0755 0o755 0.75 00".chars());
+
+        assert_eq!(lexer.next_token().unwrap(), Token::IntValue { value: BigInt::from(0) });
+        assert_eq!(
+            lexer.next_token().unwrap_err().message,
+            "Leading zeros are not allowed in integers (use 0o for octal)"
+        );
+        assert_eq!(lexer.next_token().unwrap(), Token::IntValue { value: BigInt::from(0o755) });
+        assert_eq!(lexer.next_token().unwrap(), Token::FloatValue { value: 0.75 });
+        assert!(lexer.next_token().is_err());
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_lex_expression() {
+        let source = String::from("x = f(1); y = 2;");
+        let mut lexer = Lexer::new(source.chars());
+
+        assert_eq!(
+            lexer.lex_expression(false).unwrap(),
+            vec![
+                Token::Symbol { name: String::from("x") },
+                Token::Assignment,
+                Token::Symbol { name: String::from("f") },
+                Token::LeftParens,
+                Token::IntValue { value: BigInt::from(1) },
+                Token::RightParens
+            ]
+        );
+        assert_eq!(
+            lexer.lex_expression(true).unwrap(),
+            vec![
+                Token::Symbol { name: String::from("y") },
+                Token::Assignment,
+                Token::IntValue { value: BigInt::from(2) },
+                Token::Semicolon
+            ]
+        );
+        assert_eq!(lexer.lex_expression(true).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_lex_expression_nested_semicolon() {
+        let source = String::from("fn f() { a; b; } c");
+        let mut lexer = Lexer::new(source.chars());
+
+        let tokens = lexer.lex_expression(true).unwrap();
+        assert_eq!(tokens.len(), 11);
+        assert_eq!(tokens.last().unwrap(), &Token::Symbol { name: String::from("c") });
+    }
+
+    #[test]
+    fn test_layout_mode_mixed_tabs_and_spaces() {
+        let source = String::from("if a\n    x\n\ty");
+        let mut lexer = Lexer::new(source.chars()).with_layout_mode(true).with_tab_width(4);
+
+        for _ in 0..4 {
+            assert!(lexer.next_token().is_ok());
+        }
+
+        let error = lexer.next_spanned_token().unwrap_err();
+        assert_eq!(error.message, "Inconsistent use of tabs and spaces in indentation");
+        assert_eq!(error.span.start, Position { row: 2, column: 4 });
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("y"));
+
+        // Tabs are fine as long as every line of a block is indented the same way
+        let source = String::from("if a\n\tif b\n\t\tx\n\ty\nz");
+        let tokens: Vec<Token> = Lexer::new(source.chars())
+            .with_layout_mode(true)
+            .map(|token| token.unwrap())
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::If,
+                Token::symbol("a"),
+                Token::Indent,
+                Token::If,
+                Token::symbol("b"),
+                Token::Indent,
+                Token::symbol("x"),
+                Token::Dedent,
+                Token::symbol("y"),
+                Token::Dedent,
+                Token::symbol("z")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bytes_escapes() {
+        let source = String::from(r#"b"\x00\x7f\xFF" b"abc" b"\n\t\\\"\'" b"\u{e9}""#);
+        assert_eq!(
+            lex_source(&source),
+            vec![
+                Token::bytes(&[0x00, 0x7f, 0xff]),
+                Token::bytes(b"abc"),
+                Token::bytes(b"\n\t\\\"'"),
+                Token::bytes("é".as_bytes())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bytes_invalid() {
+        let cases = [
+            (r#"b"é""#, "Bytes literals may only contain ASCII characters"),
+            (r#"b"\x4""#, "Invalid escape sequence: \\x must be followed by two hex digits"),
+            (r#"b"\q""#, "Invalid escape sequence"),
+            (r#"b"\u{110000}""#, "Invalid escape sequence: not a unicode scalar value"),
+            (r#"b"\u{}""#, "Invalid escape sequence: malformed unicode escape"),
+        ];
+
+        for (source, message) in &cases {
+            let mut lexer = Lexer::new(source.chars());
+            assert_eq!(lexer.next_token().unwrap_err().message, *message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            Lexer::new("a = 0x1;".chars()).tokenize().unwrap(),
+            vec![Token::symbol("a"), Token::Assignment, Token::int(1), Token::Semicolon]
+        );
+        assert_eq!(Lexer::new("".chars()).tokenize().unwrap(), vec![]);
+        assert_eq!(
+            Lexer::new("a = §;".chars()).tokenize().unwrap_err().message,
+            "Failed to lex source"
+        );
+    }
+
+    #[test]
+    fn test_line_endings() {
+        for newline in &["\n", "\r\n", "\r"] {
+            let source = ["int i;", "  i = 5; # comment", "", "i"].join(newline);
+            let mut lexer = Lexer::new(source.chars());
+
+            let mut rows: Vec<usize> = Vec::new();
+            while let Some(Ok(spanned)) = lexer.try_next_spanned() {
+                rows.push(spanned.span.start.row);
+            }
+            assert_eq!(rows, vec![0, 0, 0, 1, 1, 1, 1, 3], "{:?}", newline);
+        }
+
+        let mut lexer = Lexer::new("a\r\rb".chars());
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.next_spanned_token().unwrap().span.start, Position { row: 2, column: 0 });
+    }
+
+    // A small xorshift generator, so the fuzz test below is reproducible without extra crates.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+
+        fn char(&mut self) -> char {
+            const INTERESTING: &[char] = &[
+                '"', '\'', '\\', '#', '!', '.', ':', '-', '>', '<', '=', '0', 'b', 'x', 'u', '{', '}',
+                '\n', '\r', '\t', ' ', '`', 'é', '\u{a0}', '\u{fffd}',
+            ];
+
+            if self.below(4) == 0 {
+                std::char::from_u32(self.below(0x11_0000) as u32).unwrap_or('?')
+            } else {
+                INTERESTING[self.below(INTERESTING.len())]
+            }
+        }
+    }
+
+    fn mutate(rng: &mut Rng, seed: &str) -> String {
+        let mut chars: Vec<char> = seed.chars().collect();
+
+        for _ in 0..=rng.below(8) {
+            let index = rng.below(chars.len() + 1);
+
+            match rng.below(3) {
+                0 => chars.insert(index, rng.char()),
+                1 if index < chars.len() => {
+                    chars.remove(index);
+                }
+                _ if index < chars.len() => chars[index] = rng.char(),
+                _ => chars.push(rng.char()),
+            }
+        }
+
+        chars.into_iter().collect()
+    }
+
+    // Drives a lexer to the end of its input, failing if it keeps producing tokens (or errors)
+    // without running out of characters.
+    fn lex_to_end<T: Iterator<Item = char>>(mut lexer: Lexer<T>, length: usize, source: &str) {
+        for _ in 0..(2 * length + 16) {
+            if lexer.try_next().is_none() {
+                return;
+            }
+        }
+
+        panic!("Lexer did not reach the end of {:?}", source);
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let seeds = [
+            include_str!("../../fuzz/corpus/lexer/seed.bead"),
+            include_str!("../../examples/classes.bead"),
+            include_str!("../../examples/types.bead"),
+            "",
+        ];
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for seed in &seeds {
+            for _ in 0..300 {
+                let source = mutate(&mut rng, seed);
+                let length = source.chars().count();
+
+                let _ = Lexer::new(source.chars()).tokenize();
+                lex_to_end(Lexer::new(source.chars()), length, &source);
+                lex_to_end(
+                    Lexer::new(source.chars())
+                        .with_layout_mode(true)
+                        .with_adjacent_string_concatenation(true)
+                        .with_raw_lexemes(true)
+                        .with_tab_width(4),
+                    length,
+                    &source,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let source = String::from("int total = a + \\\n    b;");
+        let mut lexer = Lexer::new(source.chars());
+        let mut tokens: Vec<Token> = Vec::new();
+
+        while let Some(Ok(spanned)) = lexer.try_next_spanned() {
+            if spanned.token == Token::symbol("b") {
+                assert_eq!(spanned.span.start, Position { row: 1, column: 4 });
+            }
+            tokens.push(spanned.token);
+        }
+        assert_eq!(
+            tokens,
+            vec![
+                Token::IntType,
+                Token::symbol("total"),
+                Token::Assignment,
+                Token::symbol("a"),
+                Token::Add,
+                Token::symbol("b"),
+                Token::Semicolon
+            ]
+        );
+
+        // In layout mode the continued line isn't indented relative to the first one
+        let source = String::from("x = \\\r\n    1\ny");
+        let tokens = Lexer::new(source.chars()).with_layout_mode(true).tokenize().unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::symbol("x"), Token::Assignment, Token::int(1), Token::symbol("y")]
+        );
+
+        let mut lexer = Lexer::new("a \\ b".chars());
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.next_token().unwrap_err().message, "Failed to lex source");
+    }
+
+    #[test]
+    fn test_access_modifiers() {
+        let source = String::from("pub class Foo {} priv fn g() {} pub construct() {} priv\tfn\nh");
+        assert_eq!(
+            lex_source(&source),
+            vec![
+                Token::Public,
+                Token::Class,
+                Token::symbol("Foo"),
+                Token::LeftCurlyBracket,
+                Token::RightCurlyBracket,
+                Token::Private,
+                Token::Function,
+                Token::symbol("g"),
+                Token::LeftParens,
+                Token::RightParens,
+                Token::LeftCurlyBracket,
+                Token::RightCurlyBracket,
+                Token::Public,
+                Token::Constructor,
+                Token::LeftParens,
+                Token::RightParens,
+                Token::LeftCurlyBracket,
+                Token::RightCurlyBracket,
+                Token::Private,
+                Token::Function,
+                Token::symbol("h")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_into() {
+        let mut tokens: Vec<Token> = Vec::new();
+
+        Lexer::new("a = 1;".chars()).lex_into(&mut tokens).unwrap();
+        Lexer::new("b".chars()).lex_into(&mut tokens).unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::symbol("a"), Token::Assignment, Token::int(1), Token::Semicolon, Token::symbol("b")]
+        );
+
+        assert!(Lexer::new("c `".chars()).lex_into(&mut tokens).is_err());
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[5], Token::symbol("c"));
+    }
+
+    #[test]
+    fn test_power() {
+        assert_eq!(
+            lex_source("a ** b * c *= d ***"),
+            vec![
+                Token::symbol("a"),
+                Token::Power,
+                Token::symbol("b"),
+                Token::Multiply,
+                Token::symbol("c"),
+                Token::MultiplyAssign,
+                Token::symbol("d"),
+                Token::Power,
+                Token::Multiply
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_each() {
+        let mut count = 0;
+        Lexer::new("let x = [1, 2];".chars()).lex_each(|_| count += 1).unwrap();
+        assert_eq!(count, 9);
+
+        let mut seen: Vec<Token> = Vec::new();
+        assert!(Lexer::new("a b `".chars()).lex_each(|token| seen.push(token)).is_err());
+        assert_eq!(seen, vec![Token::symbol("a"), Token::symbol("b")]);
+    }
+
+    #[test]
+    fn test_multiline_string_positions() {
+        let mut lexer = Lexer::new("x = \"one\ntwo\" y".chars());
+        let mut spans: Vec<Span> = Vec::new();
+
+        while let Some(spanned) = lexer.try_next_spanned() {
+            spans.push(spanned.unwrap().span);
+        }
+
+        assert_eq!(spans.len(), 4);
+        assert_eq!(spans[2].start, Position { row: 0, column: 4 });
+        assert_eq!(spans[2].end, Position { row: 1, column: 4 });
+        assert_eq!(spans[3].start, Position { row: 1, column: 5 });
+
+        assert_eq!(
+            lex_source("\"a\r\nb\rc\"\nd"),
+            vec![Token::string("a\r\nb\rc"), Token::symbol("d")]
+        );
+
+        let mut lexer = Lexer::new("\"a\r\nb\rc\" d".chars());
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.next_spanned_token().unwrap().span.start, Position { row: 2, column: 3 });
+    }
+
+    #[test]
+    fn test_parameterized_container_types() {
+        assert_eq!(
+            lex_source("list[int]"),
+            vec![Token::ListType, Token::LeftSquareBracket, Token::IntType, Token::RightSquareBracket]
+        );
+
+        assert_eq!(
+            lex_source("dict[str,int]"),
+            vec![
+                Token::DictType,
+                Token::LeftSquareBracket,
+                Token::StringType,
+                Token::Comma,
+                Token::IntType,
+                Token::RightSquareBracket,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trivia() {
+        let source = "# The answer\n\n\n# Really\nint x = 1; # trailing\ny\n\n# The end\n";
+        let tokens = Lexer::new(source.chars()).lex_tokens_with_trivia().unwrap();
+
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(tokens[0].token, Token::IntType);
+        assert_eq!(
+            tokens[0].leading_trivia,
+            vec![
+                Trivia::Comment(String::from(" The answer")),
+                Trivia::BlankLines(2),
+                Trivia::Comment(String::from(" Really")),
+            ]
+        );
+        assert!(tokens[1].leading_trivia.is_empty());
+        assert_eq!(tokens[5].token, Token::symbol("y"));
+        assert_eq!(tokens[5].leading_trivia, vec![Trivia::Comment(String::from(" trailing"))]);
+
+        assert_eq!(tokens[6].token, Token::Eof);
+        assert_eq!(
+            tokens[6].leading_trivia,
+            vec![Trivia::BlankLines(1), Trivia::Comment(String::from(" The end"))]
+        );
+
+        // Trivia is dropped unless asked for
+        let mut lexer = Lexer::new(source.chars());
+        assert!(lexer.next_spanned_token().unwrap().leading_trivia.is_empty());
+    }
+
+    #[test]
+    fn test_method_modifiers() {
+        assert_eq!(
+            lex_source("virtual fn foo() -> int;"),
+            vec![
+                Token::Virtual,
+                Token::Function,
+                Token::symbol("foo"),
+                Token::LeftParens,
+                Token::RightParens,
+                Token::FnReturnTypeDelim,
+                Token::IntType,
+                Token::Semicolon,
+            ]
+        );
+
+        assert_eq!(
+            lex_source("override fn foo() -> int {} abstract"),
+            vec![
+                Token::Override,
+                Token::Function,
+                Token::symbol("foo"),
+                Token::LeftParens,
+                Token::RightParens,
+                Token::FnReturnTypeDelim,
+                Token::IntType,
+                Token::LeftCurlyBracket,
+                Token::RightCurlyBracket,
+                Token::Abstract,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_static() {
+        assert_eq!(
+            lex_source("class Counter { static int count = 0; } Counter::count"),
+            vec![
+                Token::Class,
+                Token::symbol("Counter"),
+                Token::LeftCurlyBracket,
+                Token::Static,
+                Token::IntType,
+                Token::symbol("count"),
+                Token::Assignment,
+                Token::int(0),
+                Token::Semicolon,
+                Token::RightCurlyBracket,
+                Token::symbol("Counter"),
+                Token::StaticAccessor,
+                Token::symbol("count"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut lexer = Lexer::new("a = b + 1;".chars());
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+        assert_eq!(lexer.next_token().unwrap(), Token::Assignment);
+
+        let mut speculative = lexer.clone();
+        assert_eq!(speculative.next_token().unwrap(), Token::symbol("b"));
+        assert_eq!(speculative.next_token().unwrap(), Token::Add);
+        assert_eq!(speculative.next_spanned_token().unwrap().span.start, Position { row: 0, column: 8 });
+
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("b"));
+        assert_eq!(speculative.next_token().unwrap(), Token::Semicolon);
+        assert_eq!(lexer.next_token().unwrap(), Token::Add);
+        assert_eq!(speculative.next_token().unwrap(), Token::Eof);
+        assert_eq!(lexer.next_token().unwrap(), Token::int(1));
+
+        // Neither the lexer nor its clone keeps consumed chars around without a checkpoint
+        let source = "x ".repeat(1000);
+        let mut lexer = Lexer::new(source.chars());
+        let mut clone = lexer.clone();
+        while lexer.try_next().is_some() {}
+        while clone.try_next().is_some() {}
+        assert!(lexer.history.is_empty() && clone.history.is_empty());
+
+        // Nor do checkpoints of the clone keep the original recording
+        let mut lexer = Lexer::new(source.chars());
+        let clone = lexer.clone();
+        let _checkpoint = clone.checkpoint();
+        while lexer.try_next().is_some() {}
+        assert!(lexer.history.is_empty());
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut lexer = Lexer::new("x = foo(1, 2.5);\ny".chars());
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("x"));
+
+        let checkpoint = lexer.checkpoint();
+        let mut lexed: Vec<SpannedToken> = Vec::new();
+        for _ in 0..5 {
+            lexed.push(lexer.next_spanned_token().unwrap());
+        }
+        assert_eq!(lexed[4].token, Token::Comma);
+
+        lexer.restore(checkpoint);
+        for spanned in lexed {
+            assert_eq!(lexer.next_spanned_token().unwrap(), spanned);
+        }
+
+        // Nested checkpoints, rewinding the inner one before the outer one
+        let outer = lexer.checkpoint();
+        assert_eq!(lexer.next_token().unwrap(), Token::float(2.5));
+        let inner = lexer.checkpoint();
+        assert_eq!(lexer.next_token().unwrap(), Token::RightParens);
+        lexer.restore(inner);
+        assert_eq!(lexer.next_token().unwrap(), Token::RightParens);
+        lexer.restore(outer);
+
+        assert_eq!(
+            lexer.tokenize().unwrap(),
+            vec![Token::float(2.5), Token::RightParens, Token::Semicolon, Token::symbol("y")]
+        );
+    }
+
+    #[test]
+    fn test_newline_statement_terminator() {
+        let with_semicolons = "x = 1;\nfn f(a,\n  b) {\n  return a + \\\n  b;\n};\n\ny = [1,\n 2];\nz = 3;";
+        let with_newlines = "x = 1\nfn f(a,\n  b) {\n  return a + \\\n  b\n}\n\ny = [1,\n 2]\nz = 3";
+
+        let expected = lex_source(with_semicolons);
+        let tokens = Lexer::new(with_newlines.chars())
+            .with_statement_terminator(StatementTerminator::Newline)
+            .tokenize()
+            .unwrap();
+        assert_eq!(tokens, expected);
+
+        // Attributes can span several lines too
+        let tokens = Lexer::new("#[a,\nb]\nf".chars())
+            .with_statement_terminator(StatementTerminator::Newline)
+            .tokenize()
+            .unwrap();
+        assert_eq!(tokens, lex_source("#[a, b]; f;"));
+
+        // Newlines are insignificant by default
+        assert_eq!(lex_source("x = 1\ny = 2"), lex_source("x = 1 y = 2"));
+
+        let mut lexer =
+            Lexer::new("x # comment\ny".chars()).with_statement_terminator(StatementTerminator::Newline);
+        lexer.next_token().unwrap();
+        let terminator = lexer.next_spanned_token().unwrap();
+        assert_eq!(terminator.token, Token::Semicolon);
+        assert_eq!(terminator.span.start, Position { row: 0, column: 11 });
+        assert_eq!(terminator.span.end, terminator.span.start);
+    }
+
+    #[test]
+    fn test_sort_errors_by_span() {
+        let mut errors: Vec<LexerError> = Lexer::new("a § b\n¬ c\n  §".chars())
+            .filter_map(Result::err)
+            .collect();
+        assert_eq!(errors.len(), 3);
+
+        errors.reverse();
+        errors.sort_by_key(|error| error.span);
+
+        let starts: Vec<Position> = errors.iter().map(|error| error.span.start).collect();
+        assert_eq!(
+            starts,
+            vec![
+                Position { row: 0, column: 2 },
+                Position { row: 1, column: 0 },
+                Position { row: 2, column: 2 },
+            ]
+        );
+        assert!(errors[0].span < errors[1].span);
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(
+            lex_source("1.5..2.5"),
+            vec![Token::float(1.5), Token::RangeExclusive, Token::float(2.5)]
+        );
+        assert_eq!(
+            lex_source("0..10 a..b 1..x.len"),
+            vec![
+                Token::int(0),
+                Token::RangeExclusive,
+                Token::int(10),
+                Token::symbol("a"),
+                Token::RangeExclusive,
+                Token::symbol("b"),
+                Token::int(1),
+                Token::RangeExclusive,
+                Token::symbol("x"),
+                Token::MemberAccessor,
+                Token::symbol("len"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_introspection_keywords() {
+        assert_eq!(
+            lex_source("typeof(x) sizeof(int)"),
+            vec![
+                Token::Typeof,
+                Token::LeftParens,
+                Token::symbol("x"),
+                Token::RightParens,
+                Token::Sizeof,
+                Token::LeftParens,
+                Token::IntType,
+                Token::RightParens,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keywords() {
+        let first = keywords();
+        assert_eq!(first, keywords());
+
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(first, sorted);
+
+        assert!(first.contains(&String::from("class")));
+        assert!(first.contains(&String::from("null")));
+        assert!(!first.contains(&String::from("foo")));
+    }
+
+    #[test]
+    fn test_raw_identifiers() {
+        assert_eq!(
+            lex_source("r#class class r#int = r#_x; r"),
+            vec![
+                Token::symbol("class"),
+                Token::Class,
+                Token::symbol("int"),
+                Token::Assignment,
+                Token::symbol("_x"),
+                Token::Semicolon,
+                Token::symbol("r"),
+            ]
+        );
+
+        // Not followed by a letter, so it's still a comment
+        assert_eq!(
+            lex_source("r#1 class\nr # class\nr#"),
+            vec![Token::symbol("r"), Token::symbol("r"), Token::symbol("r")]
+        );
+
+        let mut lexer = Lexer::new("r#fn(".chars()).with_raw_lexemes(true);
+        let spanned = lexer.next_spanned_token().unwrap();
+        assert_eq!(spanned.raw, Some(String::from("r#fn")));
+        assert_eq!(spanned.span.end, Position { row: 0, column: 4 });
+        assert_eq!(lexer.next_token().unwrap(), Token::LeftParens);
+    }
+
+    #[test]
+    fn test_char_escapes() {
+        assert_eq!(
+            lex_source("'\\n' '\\t' '\\'' '\\\\' '\\x41' '\\u{1F600}' '\"'"),
+            vec![
+                Token::char('\n'),
+                Token::char('\t'),
+                Token::char('\''),
+                Token::char('\\'),
+                Token::char('A'),
+                Token::char('\u{1F600}'),
+                Token::char('"'),
+            ]
+        );
+
+        for source in &["'\n'", "'\r'", "'\t'"] {
+            let err = Lexer::new(source.chars()).next_token().unwrap_err();
+            assert!(err.message.contains("raw newline or tab"), "{}", err.message);
+        }
+
+        assert!(Lexer::new("'\\xff'".chars()).next_token().is_err());
+        assert!(Lexer::new("'\\q'".chars()).next_token().is_err());
+    }
+
+    #[test]
+    fn test_errors_are_fatal() {
+        let source = "a § b";
+
+        let mut lexer = Lexer::new(source.chars());
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+        assert!(lexer.next_token().is_err());
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("b"));
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+
+        let mut lexer = Lexer::new(source.chars()).errors_are_fatal(true);
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+        assert!(lexer.next_token().is_err());
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+        assert!(lexer.next().is_none());
+
+        let results: Vec<Result<Token, LexerError>> =
+            Lexer::new(source.chars()).errors_are_fatal(true).collect();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_plus_sign() {
+        assert_eq!(lex_source("+5"), vec![Token::Add, Token::int(5)]);
+        assert_eq!(lex_source("a + 5"), vec![Token::symbol("a"), Token::Add, Token::int(5)]);
+        assert_eq!(
+            lex_source("a + +5 a++5 a+=+5"),
+            vec![
+                Token::symbol("a"),
+                Token::Add,
+                Token::Add,
+                Token::int(5),
+                Token::symbol("a"),
+                Token::Add,
+                Token::Add,
+                Token::int(5),
+                Token::symbol("a"),
+                Token::AddAssign,
+                Token::Add,
+                Token::int(5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ellipsis() {
+        assert_eq!(
+            lex_source("f(args...) a..b x.y ....."),
+            vec![
+                Token::symbol("f"),
+                Token::LeftParens,
+                Token::symbol("args"),
+                Token::Ellipsis,
+                Token::RightParens,
+                Token::symbol("a"),
+                Token::RangeExclusive,
+                Token::symbol("b"),
+                Token::symbol("x"),
+                Token::MemberAccessor,
+                Token::symbol("y"),
+                Token::Ellipsis,
+                Token::RangeExclusive,
+            ]
+        );
+        assert_eq!(lex_source("1...2"), vec![Token::int(1), Token::Ellipsis, Token::int(2)]);
+        assert_eq!(lex_source("."), vec![Token::MemberAccessor]);
+        assert_eq!(lex_source(".."), vec![Token::RangeExclusive]);
+    }
+
+    #[test]
+    fn test_case_insensitive_keywords() {
+        let tokens = Lexer::new("IF If if Foo TRUE r#CLASS".chars())
+            .with_case_insensitive_keywords(true)
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::If, Token::If, Token::If, Token::symbol("Foo"), Token::bool(true), Token::symbol("CLASS")]
+        );
+
+        assert_eq!(lex_source("IF If if"), vec![Token::symbol("IF"), Token::symbol("If"), Token::If]);
+    }
+
+    #[test]
+    fn test_backtick() {
+        let mut lexer = Lexer::new("a `b`".chars());
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.message, "Unexpected backtick");
+        assert_eq!(err.span.start, Position { row: 0, column: 2 });
+        assert_eq!(err.span.end, Position { row: 0, column: 3 });
+
+        // The backtick itself is skipped
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("b"));
+        assert_eq!(lexer.next_token().unwrap_err().span.start, Position { row: 0, column: 4 });
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_attributes() {
+        assert_eq!(
+            lex_source("#[inline]\nfn f() {} # [not an attribute]\n#comment\n#[derive(Eq)]"),
+            vec![
+                Token::HashBracket,
+                Token::symbol("inline"),
+                Token::RightSquareBracket,
+                Token::Function,
+                Token::symbol("f"),
+                Token::LeftParens,
+                Token::RightParens,
+                Token::LeftCurlyBracket,
+                Token::RightCurlyBracket,
+                Token::HashBracket,
+                Token::symbol("derive"),
+                Token::LeftParens,
+                Token::symbol("Eq"),
+                Token::RightParens,
+                Token::RightSquareBracket,
+            ]
+        );
+
+        let mut lexer = Lexer::new("#[x]".chars());
+        assert_eq!(lexer.next_spanned_token().unwrap().span.end, Position { row: 0, column: 2 });
+    }
+
+    #[test]
+    fn test_word_operators() {
+        assert_eq!(lex_source("a and b"), lex_source("a && b"));
+        assert_eq!(lex_source("a or not b"), lex_source("a || !b"));
+        assert_eq!(
+            lex_source("not (x and y) or z"),
+            vec![
+                Token::Not,
+                Token::LeftParens,
+                Token::symbol("x"),
+                Token::LogicalAnd,
+                Token::symbol("y"),
+                Token::RightParens,
+                Token::LogicalOr,
+                Token::symbol("z"),
+            ]
+        );
+        assert_eq!(lex_source("android nothing"), vec![Token::symbol("android"), Token::symbol("nothing")]);
+    }
+
+    #[test]
+    fn test_underscore() {
+        assert_eq!(
+            lex_source("_ _foo foo_ __ (_, x) r#_"),
+            vec![
+                Token::Underscore,
+                Token::symbol("_foo"),
+                Token::symbol("foo_"),
+                Token::symbol("__"),
+                Token::LeftParens,
+                Token::Underscore,
+                Token::Comma,
+                Token::symbol("x"),
+                Token::RightParens,
+                Token::symbol("_"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_escapes_and_line_joining() {
+        let mut lexer = Lexer::new("x = \"a long \\\nstring\\t\\\"q\\\" \\\r\nend\" y".chars());
+        lexer.next_token().unwrap();
+        lexer.next_token().unwrap();
+
+        let string = lexer.next_spanned_token().unwrap();
+        assert_eq!(string.token, Token::string("a long string\t\"q\" end"));
+        assert_eq!(string.span.end, Position { row: 2, column: 4 });
+        assert_eq!(lexer.next_spanned_token().unwrap().span.start, Position { row: 2, column: 5 });
+
+        assert_eq!(lex_source("\"\\x41\\u{e9}\\\\\""), vec![Token::string("A\u{e9}\\")]);
+        assert!(Lexer::new("\"\\xff\"".chars()).next_token().is_err());
+        assert!(Lexer::new("\"\\q\"".chars()).next_token().is_err());
+    }
+
+    #[test]
+    fn test_lexing_resumes_after_invalid_literals() {
+        let cases = [
+            "\"\\q\" x \"y\"",
+            "\"a\\q \\\" b\nc\" x \"y\"",
+            "'\\q' x \"y\"",
+            "b\"\\q\" x \"y\"",
+            "b\"é\" x \"y\"",
+            "f\"{a}\\q{b}\" x \"y\"",
+            "f\"a}\" x \"y\"",
+        ];
+
+        for source in &cases {
+            let mut lexer = Lexer::new(source.chars());
+            assert!(lexer.next_token().is_err(), "{}", source);
+            assert_eq!(lexer.next_token().unwrap(), Token::symbol("x"), "{}", source);
+            assert_eq!(lexer.next_token().unwrap(), Token::string("y"), "{}", source);
+            assert_eq!(lexer.next_token().unwrap(), Token::Eof, "{}", source);
+        }
+
+        // Char literals can't span lines, so an unclosed one ends with its line
+        let mut lexer = Lexer::new("'\\q x\ny".chars());
+        assert!(lexer.next_token().is_err());
+        let spanned = lexer.next_spanned_token().unwrap();
+        assert_eq!(spanned.token, Token::symbol("y"));
+        assert_eq!(spanned.span.start, Position { row: 1, column: 0 });
+    }
+
+    #[test]
+    fn test_pipe_forward() {
+        assert_eq!(
+            lex_source("x |> f |> g a || b | c |>> | >"),
+            vec![
+                Token::symbol("x"),
+                Token::PipeForward,
+                Token::symbol("f"),
+                Token::PipeForward,
+                Token::symbol("g"),
+                Token::symbol("a"),
+                Token::LogicalOr,
+                Token::symbol("b"),
+                Token::BitwiseOr,
+                Token::symbol("c"),
+                Token::PipeForward,
+                Token::Greater,
+                Token::BitwiseOr,
+                Token::Greater,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operators_at_end_of_input() {
+        let operators = vec![
+            ("+", Token::Add),
+            ("-", Token::Subtract),
+            ("*", Token::Multiply),
+            ("**", Token::Power),
+            ("/", Token::Divide),
+            ("%", Token::Modulo),
+            ("^", Token::BitwiseXor),
+            ("~", Token::BitwiseNot),
+            ("!", Token::Not),
+            ("=", Token::Assignment),
+            ("|", Token::BitwiseOr),
+            ("&", Token::BitwiseAnd),
+            (">", Token::Greater),
+            ("<", Token::Less),
+            ("+=", Token::AddAssign),
+            ("==", Token::Equals),
+            ("||", Token::LogicalOr),
+            ("|>", Token::PipeForward),
+            (">>", Token::BitwiseRightShift),
+            ("<=", Token::LessEqual),
+            ("->", Token::FnReturnTypeDelim),
+        ];
+
+        for (operator, expected) in operators {
+            for source in &[format!("a {}", operator), format!("a{}", operator)] {
+                let mut lexer = Lexer::new(source.chars());
+
+                assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"), "{}", source);
+                assert_eq!(lexer.next_token().unwrap(), expected, "{}", source);
+                assert_eq!(lexer.next_token().unwrap(), Token::Eof, "{}", source);
+                assert_eq!(lexer.next_token().unwrap(), Token::Eof, "{}", source);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ascii_whitespace() {
+        let source = "a\u{a0}b \t\r\nc";
+
+        assert_eq!(lex_source(source), vec![Token::symbol("a"), Token::symbol("b"), Token::symbol("c")]);
+
+        let mut lexer = Lexer::new(source.chars()).with_ascii_whitespace(true);
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.message, "Unexpected non-ASCII whitespace");
+        assert_eq!(err.span.start, Position { row: 0, column: 1 });
+
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("b"));
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("c"));
+
+        // Form feeds and vertical tabs are ASCII too
+        let lexer = Lexer::new("a\u{c}b\u{b}c".chars()).with_ascii_whitespace(true);
+        assert_eq!(
+            lexer.tokenize().unwrap(),
+            vec![Token::symbol("a"), Token::symbol("b"), Token::symbol("c")]
+        );
+    }
+
+    #[test]
+    fn test_to_source_round_trip() {
+        let source = "class A { pub fn f(r#int x, str s) -> float { return 0x1F + 1.50 ** x |> g; } }\n\
+                      l = [1, 2.0, 'q', '\\'', '\\n', \"a\\tb\\\"c\\\\\", b\"\\x00\\\"z\", null, true]; \
+                      x := a..b; f(xs...); and or not _ #[inline] a::b; \
+                      s = f\"a{b}c\" + f\"{{\\t{ g(\"}\") }{h}}}\\\"\"";
+        let reconstruct = |source: &str, raw: bool| -> String {
+            let stream = Lexer::new(source.chars()).with_raw_lexemes(raw).lex_with_spans().unwrap();
+            let mut reconstructed = String::from("");
+            let mut previous: Option<&Token> = None;
+
+            // Spaces inside a format string (outside of its holes) would become part of it
+            for spanned in &stream {
+                let glued = matches!(
+                    previous,
+                    Some(Token::FormatStringStart) | Some(Token::FormatHoleEnd)
+                ) || matches!(
+                    spanned.token,
+                    Token::FormatStringEnd | Token::FormatHoleStart
+                );
+
+                if previous.is_some() && !glued {
+                    reconstructed.push(' ');
+                }
+
+                reconstructed.push_str(&spanned.to_source());
+                previous = Some(&spanned.token);
+            }
+
+            reconstructed
+        };
+
+        // Raw lexemes are kept as written
+        let raw = reconstruct(source, true);
+        assert!(raw.contains("0x1F + 1.50") && raw.contains("r#int"), "{}", raw);
+        assert_eq!(lex_source(&raw), lex_source(source));
+
+        // Otherwise tokens are spelled canonically
+        let source = source.replace("r#int", "y");
+        let canonical = reconstruct(&source, false);
+        assert!(canonical.contains("31 + 1.5"), "{}", canonical);
+        assert_eq!(lex_source(&canonical), lex_source(&source));
+
+        assert!(canonical.ends_with("s = f\"a{ b }c\" + f\"{{\\t{ g ( \"}\" ) }{ h }}}\\\"\""), "{}", canonical);
+
+        assert_eq!(Token::float(1e16).to_source(), "10000000000000000.0");
+        assert_eq!(Token::LogicalAnd.to_source(), "&&");
+        assert_eq!(Token::Eof.to_source(), "");
+    }
+
+    #[test]
+    fn test_builtin_types() {
+        let types = builtin_types();
+        assert_eq!(
+            types,
+            vec!["int", "float", "str", "char", "bool", "bytes", "tuple", "enum", "list", "dict"]
+        );
+
+        for name in types {
+            let token = Lexer::new(name.chars()).next_token().unwrap();
+            assert!(token.is_type(), "{}", name);
+            assert!(keywords().contains(&String::from(name)));
+        }
+
+        assert!(!Token::If.is_type());
+        assert!(!Token::NullValue.is_type());
+        assert!(!Token::int(1).is_type());
+    }
+
+    #[test]
+    fn test_escaped_single_quote() {
+        let mut lexer = Lexer::new("c = '\\'';".chars());
+        lexer.next_token().unwrap();
+        lexer.next_token().unwrap();
+
+        let chr = lexer.next_spanned_token().unwrap();
+        assert_eq!(chr.token, Token::char('\''));
+        assert_eq!(chr.span.end, Position { row: 0, column: 8 });
+        assert_eq!(lexer.next_token().unwrap(), Token::Semicolon);
+
+        // The escaped quote doesn't close the literal
+        assert!(Lexer::new("'\\'".chars()).next_token().is_err());
+        assert_eq!(lex_source("'\\'' '\\''"), vec![Token::char('\''), Token::char('\'')]);
+    }
+
+    #[test]
+    fn test_dollar() {
+        assert_eq!(
+            lex_source("$x $ (y)$"),
+            vec![
+                Token::Dollar,
+                Token::symbol("x"),
+                Token::Dollar,
+                Token::LeftParens,
+                Token::symbol("y"),
+                Token::RightParens,
+                Token::Dollar,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_guard_keywords() {
+        assert_eq!(
+            lex_source("unless x { } until y { } when"),
+            vec![
+                Token::Unless,
+                Token::symbol("x"),
+                Token::LeftCurlyBracket,
+                Token::RightCurlyBracket,
+                Token::Until,
+                Token::symbol("y"),
+                Token::LeftCurlyBracket,
+                Token::RightCurlyBracket,
+                Token::When,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zero_width_space() {
+        let mut lexer = Lexer::new("a\n b\u{200b}= c".chars());
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("b"));
+
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.message, "Unexpected zero-width character");
+        assert_eq!(
+            err.span,
+            Span {
+                start: Position { row: 1, column: 2 },
+                end: Position { row: 1, column: 3 },
+            }
+        );
+
+        assert_eq!(lexer.next_token().unwrap(), Token::Assignment);
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("c"));
+
+        // Except for a byte order mark starting the input
+        let stream = Lexer::new("\u{feff}int x;".chars()).lex_with_spans().unwrap();
+        assert_eq!(stream.peek_at(0), Some(&Token::IntType));
+        assert_eq!(stream[1].span.start, Position { row: 0, column: 4 });
+        assert_eq!(
+            Lexer::new("\u{feff}#!/bin/bead\nx".chars()).tokenize().unwrap(),
+            vec![Token::symbol("x")]
+        );
+        assert!(Lexer::new("x\u{feff}".chars()).tokenize().is_err());
+        assert!(Lexer::new("\u{feff}\u{feff}x".chars()).tokenize().is_err());
     }
 
-    fn handle_delimiter(&mut self) -> Result<Token, LexerError> {
-        return match self.current_chr.unwrap() {
-            '(' => Ok(Token::LeftParens),
-            ')' => Ok(Token::RightParens),
-            '{' => Ok(Token::LeftCurlyBracket),
-            '}' => Ok(Token::RightCurlyBracket),
-            '[' => Ok(Token::LeftSquareBracket),
-            ']' => Ok(Token::RightSquareBracket),
-            ';' => Ok(Token::Semicolon),
-            ',' => Ok(Token::Comma),
-            '.' => Ok(Token::MemberAccessor),
-            '-' => {
-                return match self.input.peek() {
-                    Some('>') => {
-                        self.next_char();
-                        return Ok(Token::FnReturnTypeDelim);
-                    },
-                    _ => Err(LexerError {
-                        message: String::from("Could not parse delimiter"),
-                    })
-                }
-            },
-            ':' => {
-                return match self.input.peek() {
-                    Some(':') => {
-                        self.next_char();
-                        return Ok(Token::StaticAccessor);
-                    },
-                    _ => Err(LexerError {
-                        message: String::from("Could not parse delimiter"),
-                    })
-                }
-            },
-            _ => Err(LexerError {
-                message: String::from("Could not parse delimiter"),
-            })
-        }
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_tokenize_file() {
+        let path = std::env::temp_dir().join(format!("bead-tokenize-file-{}.bead", std::process::id()));
+        // Saved with a byte order mark, as some editors do
+        std::fs::write(&path, "\u{feff}int x = 5;\n").unwrap();
+
+        let tokens = Lexer::tokenize_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            tokens.unwrap(),
+            vec![Token::IntType, Token::symbol("x"), Token::Assignment, Token::int(5), Token::Semicolon]
+        );
+
+        let err = Lexer::tokenize_file(&path).unwrap_err();
+        assert!(err.message.starts_with("Failed to read "), "{}", err.message);
+        assert_eq!(err.span.start, Position { row: 0, column: 0 });
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::parser::errors::LexerError;
-    use crate::parser::lexer::Lexer;
-    use crate::parser::token::Token;
-    use num_bigint::BigInt;
+    #[test]
+    fn test_pow_assign() {
+        assert_eq!(
+            lex_source("a * b ** c *= d **= e"),
+            vec![
+                Token::symbol("a"),
+                Token::Multiply,
+                Token::symbol("b"),
+                Token::Power,
+                Token::symbol("c"),
+                Token::MultiplyAssign,
+                Token::symbol("d"),
+                Token::PowAssign,
+                Token::symbol("e"),
+            ]
+        );
+        assert_eq!(
+            lex_source("**=*=***="),
+            vec![Token::PowAssign, Token::MultiplyAssign, Token::Power, Token::MultiplyAssign]
+        );
+        assert!(Token::PowAssign.is_right_associative());
+    }
 
-    pub fn lex_source(source: &String) -> Vec<Token> {
-        let mut lexer = Lexer::new(source.chars());
-        let mut tokens: Vec<Token> = Vec::new();
-        let mut token: Result<Token, LexerError> = lexer.next_token();
+    #[test]
+    fn test_count() {
+        let source = include_str!("../../examples/types.bead");
 
-        while token.is_ok() {
-            tokens.push(token.unwrap());
-            token = lexer.next_token();
+        let tokens = Lexer::new(source.chars()).tokenize().unwrap();
+        assert_eq!(Lexer::new(source.chars()).count().unwrap(), tokens.len());
+        assert_eq!(Lexer::new("".chars()).count().unwrap(), 0);
+        assert!(Lexer::new("a § b".chars()).count().is_err());
+    }
+
+    #[test]
+    fn test_adjacent_chars() {
+        assert_eq!(lex_source("'a''b'"), vec![Token::char('a'), Token::char('b')]);
+
+        let mut lexer = Lexer::new("'a''\\n'x".chars());
+        let first = lexer.next_spanned_token().unwrap();
+        let second = lexer.next_spanned_token().unwrap();
+        assert_eq!(first.span.end, Position { row: 0, column: 3 });
+        assert_eq!(second.token, Token::char('\n'));
+        assert_eq!(second.span.start, Position { row: 0, column: 3 });
+        assert_eq!(second.span.end, Position { row: 0, column: 7 });
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("x"));
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        assert_eq!(
+            lex_source("0xDEAD_BEEF 0b1111_0000 0B11 0o7_7 1_000_000 1_0.2_5"),
+            vec![
+                Token::int(0xdead_beef_u32),
+                Token::int(0b1111_0000),
+                Token::int(3),
+                Token::int(0o77),
+                Token::int(1_000_000),
+                Token::float(10.25),
+            ]
+        );
+
+        for source in &["0x_FF", "0xFF_", "0b1__0", "1__000", "1_", "1_.5", "0b_"] {
+            assert_eq!(
+                Lexer::new(source.chars()).next_token().unwrap_err().message,
+                "Underscores in numbers may only appear between digits",
+                "{}",
+                source
+            );
         }
 
-        tokens
+        // Not numbers to begin with
+        assert_eq!(lex_source("1._5"), vec![Token::int(1), Token::MemberAccessor, Token::symbol("_5")]);
+        assert_eq!(lex_source("_1"), vec![Token::symbol("_1")]);
     }
 
     #[test]
-    fn test_class_structure() {
-        let source = String::from(r#"
-            class Logger {
-                priv str name;
+    fn test_warnings() {
+        let mut lexer = Lexer::new("x = 007.5 + 0.5;".chars());
+        let tokens: Vec<Token> = lexer.by_ref().map(Result::unwrap).collect();
 
-                fn construct(str name) {
-                    self.name = name;
-                }
-                
-                fn destruct() {
-                    int i = 76;
-                }
+        assert_eq!(tokens[2], Token::float(7.5));
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(
+            lexer.warnings(),
+            &[LexerWarning {
+                message: String::from("Redundant leading zero in float"),
+                span: Span {
+                    start: Position { row: 0, column: 4 },
+                    end: Position { row: 0, column: 9 },
+                },
+            }]
+        );
+
+        let mut lexer = Lexer::new("0.5 10.05 0x01".chars());
+        assert_eq!(lexer.by_ref().count(), 3);
+        assert!(lexer.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_automatic_statement_terminator() {
+        let lex_automatic = |source: &str| {
+            Lexer::new(source.chars())
+                .with_statement_terminator(StatementTerminator::Automatic)
+                .tokenize()
+                .unwrap()
+        };
 
-                fn clone() -> Logger;
-            }
-        "#);
-        let tokens = lex_source(&source);
         assert_eq!(
-            tokens,
+            lex_automatic("x = f(a,\n  b)\ny = [1,\n 2]\nz = a +\n  b;\nreturn\n"),
+            lex_source("x = f(a, b); y = [1, 2]; z = a + b; return;")
+        );
+
+        assert_eq!(lex_automatic("fn f() {\n  g()\n}\nh"), lex_source("fn f() { g(); }; h;"));
+        assert_eq!(lex_automatic("x = 1;\n\n# comment\ny"), lex_source("x = 1; y;"));
+    }
+
+    #[test]
+    fn test_enum_body() {
+        assert_eq!(
+            lex_source("enum Color { RED = 0, GREEN=1,BLUE }"),
             vec![
-                Token::Class,
-                Token::Symbol { name: String::from("Logger") },
-                Token::LeftCurlyBracket,
-                Token::Private,
-                Token::StringType,
-                Token::Symbol { name: String::from("name") },
-                Token::Semicolon,
-                Token::Function,
-                Token::Constructor,
-                Token::LeftParens,
-                Token::StringType,
-                Token::Symbol { name: String::from("name") },
-                Token::RightParens,
+                Token::EnumType,
+                Token::symbol("Color"),
                 Token::LeftCurlyBracket,
-                Token::SelfInstance,
-                Token::MemberAccessor,
-                Token::Symbol { name: String::from("name") },
+                Token::symbol("RED"),
                 Token::Assignment,
-                Token::Symbol { name: String::from("name") },
-                Token::Semicolon,
-                Token::RightCurlyBracket,
-                Token::Function,
-                Token::Destructor,
-                Token::LeftParens,
-                Token::RightParens,
-                Token::LeftCurlyBracket,
-                Token::IntType,
-                Token::Symbol { name: String::from("i") },
+                Token::int(0),
+                Token::Comma,
+                Token::symbol("GREEN"),
                 Token::Assignment,
-                Token::IntValue { value: BigInt::from(76) },
-                Token::Semicolon,
+                Token::int(1),
+                Token::Comma,
+                Token::symbol("BLUE"),
                 Token::RightCurlyBracket,
-                Token::Function,
-                Token::Symbol { name: String::from("clone") },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skip_until() {
+        let mut lexer = Lexer::new("x = 1 2 3; y = 4; }".chars());
+        lexer.skip_until(|token| *token == Token::Semicolon).unwrap();
+
+        let semicolon = lexer.next_spanned_token().unwrap();
+        assert_eq!(semicolon.token, Token::Semicolon);
+        assert_eq!(semicolon.span.start, Position { row: 0, column: 9 });
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("y"));
+
+        lexer.skip_until(|token| *token == Token::RightCurlyBracket).unwrap();
+        assert_eq!(lexer.next_token().unwrap(), Token::RightCurlyBracket);
+
+        lexer.skip_until(|token| *token == Token::Semicolon).unwrap();
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+        assert!(Lexer::new("a § ;".chars()).skip_until(|token| *token == Token::Semicolon).is_err());
+    }
+
+    #[test]
+    fn test_replacement_char() {
+        let mut lexer = Lexer::new("a\u{fffd}b".chars());
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+        assert!(lexer.next_token().unwrap_err().message.contains("U+FFFD"));
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("b"));
+
+        // Literals only reject it when asked to
+        let source = "\"a\u{fffd}\" '\u{fffd}'";
+        assert_eq!(lex_source(source), vec![Token::string("a\u{fffd}"), Token::char('\u{fffd}')]);
+
+        let err = Lexer::new("\"a\u{fffd}\"".chars())
+            .with_replacement_char_rejection(true)
+            .next_token()
+            .unwrap_err();
+        assert_eq!(err.message, REPLACEMENT_CHAR_ERROR);
+        assert_eq!(err.span.end, Position { row: 0, column: 3 });
+
+        let mut lexer = Lexer::new("'\u{fffd}'".chars()).with_replacement_char_rejection(true);
+        assert_eq!(lexer.next_token().unwrap_err().message, REPLACEMENT_CHAR_ERROR);
+    }
+
+    #[test]
+    fn test_symbol_spans() {
+        let symbol_span = |source: &str| {
+            let mut lexer = Lexer::new(source.chars());
+            let spanned = lexer.next_spanned_token().unwrap();
+            assert_eq!(spanned.token, Token::Symbol { name: String::from("foo") });
+            spanned.span
+        };
+
+        assert_eq!(
+            symbol_span("  foo;"),
+            Span {
+                start: Position { row: 0, column: 2 },
+                end: Position { row: 0, column: 5 },
+            }
+        );
+        assert_eq!(
+            symbol_span("foo()"),
+            Span {
+                start: Position { row: 0, column: 0 },
+                end: Position { row: 0, column: 3 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_defer_keyword() {
+        assert_eq!(
+            lex_source("defer cleanup();"),
+            vec![
+                Token::Defer,
+                Token::symbol("cleanup"),
                 Token::LeftParens,
                 Token::RightParens,
-                Token::FnReturnTypeDelim,
-                Token::Symbol { name: String::from("Logger") },
                 Token::Semicolon,
-                Token::RightCurlyBracket
             ]
         );
     }
 
     #[test]
-    fn test_assignment_expressions() {
-        let source = String::from(r#"
-            int i = 5;
-            float f = 3.54;
-            bool b = true;
-            str s1 = "Hello World";
-            bytes bb = b"\x34b";
-            list l = [i, f, b, s1, "some_val"];
-        "#);
-        let tokens = lex_source(&source);
+    fn test_decimal_literals() {
+        assert_eq!(
+            lex_source("1.50d 100d 0.1d; 2.5"),
+            vec![
+                Token::DecimalValue { value: String::from("1.50") },
+                Token::DecimalValue { value: String::from("100") },
+                Token::DecimalValue { value: String::from("0.1") },
+                Token::Semicolon,
+                Token::FloatValue { value: 2.5 },
+            ]
+        );
+
+        // The digits are kept exactly, trailing zeros included
+        let mut lexer = Lexer::new("12.3400d".chars());
+        let spanned = lexer.next_spanned_token().unwrap();
+        assert_eq!(spanned.token, Token::DecimalValue { value: String::from("12.3400") });
+        assert_eq!(spanned.token.to_source(), "12.3400d");
+        assert_eq!(spanned.span.end, Position { row: 0, column: 8 });
+
+        assert_eq!(lex_source("100days"), vec![Token::int(100), Token::symbol("days")]);
+
+        // Leading zeros are treated just like they are without the suffix
+        let mut lexer = Lexer::new("0755d 00.5d 0d 0.5d".chars());
+        assert_eq!(
+            lexer.next_token().unwrap_err().message,
+            "Leading zeros are not allowed in integers (use 0o for octal)"
+        );
+        assert_eq!(lexer.next_token().unwrap(), Token::DecimalValue { value: String::from("00.5") });
+        assert_eq!(lexer.warnings().len(), 1);
+        assert_eq!(lexer.warnings()[0].message, "Redundant leading zero in float");
+        assert_eq!(lexer.next_token().unwrap(), Token::DecimalValue { value: String::from("0") });
+        assert_eq!(lexer.next_token().unwrap(), Token::DecimalValue { value: String::from("0.5") });
+        assert_eq!(lexer.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_operator_and_delimiter_chars() {
+        for chr in "+-*/%!=|&^<>~?".chars() {
+            assert!(is_operator_char(chr), "{:?}", chr);
+            assert!(!is_delimiter_char(chr), "{:?}", chr);
+        }
+
+        for chr in "{}[](),;:.$".chars() {
+            assert!(is_delimiter_char(chr), "{:?}", chr);
+            assert!(!is_operator_char(chr), "{:?}", chr);
+        }
+
+        for chr in "a_0\"'#@` \n".chars() {
+            assert!(!is_operator_char(chr), "{:?}", chr);
+            assert!(!is_delimiter_char(chr), "{:?}", chr);
+        }
+    }
+
+    #[test]
+    fn test_token_kind_histogram() {
+        let mut histogram: BTreeMap<TokenKind, usize> = BTreeMap::new();
+
+        for token in lex_source("x = y + 1; z = x + x;") {
+            *histogram.entry(token.kind()).or_insert(0) += 1;
+        }
+
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            vec![
+                (TokenKind::Semicolon, 2),
+                (TokenKind::Symbol, 5),
+                (TokenKind::IntValue, 1),
+                (TokenKind::Add, 2),
+                (TokenKind::Assignment, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fat_arrow() {
+        assert_eq!(
+            lex_source("x >= 1 => y"),
+            vec![
+                Token::symbol("x"),
+                Token::GreaterEqual,
+                Token::int(1),
+                Token::FatArrow,
+                Token::symbol("y"),
+            ]
+        );
+
+        // Without spaces the leftmost operator takes the '=' first
+        assert_eq!(
+            lex_source("x>=>y =>= z"),
+            vec![
+                Token::symbol("x"),
+                Token::GreaterEqual,
+                Token::Greater,
+                Token::symbol("y"),
+                Token::FatArrow,
+                Token::Assignment,
+                Token::symbol("z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peek_n() {
+        let mut lexer = Lexer::new("x = f(1);".chars());
+
+        assert_eq!(
+            lexer.peek_n(3).unwrap(),
+            vec![&Token::symbol("x"), &Token::Assignment, &Token::symbol("f")]
+        );
+        assert_eq!(lexer.peek_n(1).unwrap(), vec![&Token::symbol("x")]);
+
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("x"));
+        assert_eq!(lexer.next_token().unwrap(), Token::Assignment);
+        let spanned = lexer.next_spanned_token().unwrap();
+        assert_eq!(spanned.token, Token::symbol("f"));
+        assert_eq!(spanned.span.start, Position { row: 0, column: 4 });
+        assert_eq!(lexer.next_token().unwrap(), Token::LeftParens);
+
+        // Running out of input cuts the lookahead short
+        assert_eq!(
+            lexer.peek_n(5).unwrap(),
+            vec![&Token::int(1), &Token::RightParens, &Token::Semicolon]
+        );
+        let rest: Result<Vec<Token>, LexerError> = std::iter::from_fn(|| lexer.try_next()).collect();
+        assert_eq!(rest.unwrap(), vec![Token::int(1), Token::RightParens, Token::Semicolon]);
+        assert!(lexer.peek_n(2).unwrap().is_empty());
+
+        // Errors are handed out in their place, past anything peeked before them
+        let mut lexer = Lexer::new("a § b".chars());
+        assert_eq!(lexer.peek_n(1).unwrap(), vec![&Token::symbol("a")]);
+        assert_eq!(lexer.peek_n(3).unwrap_err().message, "Failed to lex source");
+        assert_eq!(lexer.peek_n(3).unwrap_err().message, "Failed to lex source");
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.span.start, Position { row: 0, column: 2 });
+        assert_eq!(lexer.peek_n(2).unwrap(), vec![&Token::symbol("b")]);
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("b"));
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+
+        let mut lexer = Lexer::new("a § b".chars()).errors_are_fatal(true);
+        assert_eq!(lexer.peek_n(3).unwrap_err().message, "Failed to lex source");
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+        assert!(lexer.next_token().is_err());
+        assert!(lexer.peek_n(1).unwrap().is_empty());
+        assert_eq!(lexer.next_token().unwrap(), Token::Eof);
+    }
+
+    #[test]
+    fn test_radix_literal_fractions_and_exponents() {
+        assert_eq!(lex_source("0xFF"), vec![Token::int(0xff)]);
+
+        for (source, message) in &[
+            ("0xF.5", "Hex literals may not contain '.' or exponent"),
+            ("0x1p4", "Hex literals may not contain '.' or exponent"),
+            ("0x1P-4", "Hex literals may not contain '.' or exponent"),
+            ("0o7.0", "Octal literals may not contain '.' or exponent"),
+            ("0b1e3", "Binary literals may not contain '.' or exponent"),
+        ] {
+            let error = Lexer::new(source.chars()).next_token().unwrap_err();
+            assert_eq!(&error.message, message, "{}", source);
+        }
+
+        // Neither a member access nor a 'p' starting a name is a fraction or an exponent
+        assert_eq!(
+            lex_source("0xFF.abs 0x1 pow"),
+            vec![
+                Token::int(0xff),
+                Token::MemberAccessor,
+                Token::symbol("abs"),
+                Token::int(1),
+                Token::symbol("pow"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_identifier_length() {
+        let mut lexer = Lexer::new("abcd abcde r#abcdef _".chars()).with_max_identifier_length(4);
+
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("abcd"));
+
+        let error = lexer.next_token().unwrap_err();
+        assert_eq!(error.message, "Identifier is 5 chars long, more than the maximum of 4");
+        assert_eq!(
+            error.span,
+            Span {
+                start: Position { row: 0, column: 5 },
+                end: Position { row: 0, column: 10 },
+            }
+        );
+
+        let error = lexer.next_token().unwrap_err();
+        assert_eq!(error.message, "Identifier is 6 chars long, more than the maximum of 4");
+
+        assert_eq!(lexer.next_token().unwrap(), Token::Underscore);
+        assert_eq!(lex_source(&"x".repeat(1000)), vec![Token::symbol(&"x".repeat(1000))]);
+    }
+
+    #[test]
+    fn test_question_operators() {
+        let a = || Token::symbol("a");
+        let b = || Token::symbol("b");
+
+        assert_eq!(lex_source("a?.b"), vec![a(), Token::QuestionDot, b()]);
+        assert_eq!(lex_source("a ?? b"), vec![a(), Token::NullCoalesce, b()]);
+        assert_eq!(
+            lex_source("a ? b : c"),
+            vec![a(), Token::Question, b(), Token::Colon, Token::symbol("c")]
+        );
+        assert_eq!(lex_source("a.b"), vec![a(), Token::MemberAccessor, b()]);
+
+        assert_eq!(
+            lex_source("a???.b"),
+            vec![a(), Token::NullCoalesce, Token::QuestionDot, b()]
+        );
+    }
+
+    #[test]
+    fn test_scope_keywords() {
+        assert_eq!(
+            lex_source("global counter;"),
+            vec![Token::Global, Token::symbol("counter"), Token::Semicolon]
+        );
+        assert_eq!(
+            lex_source("nonlocal x;"),
+            vec![Token::Nonlocal, Token::symbol("x"), Token::Semicolon]
+        );
+    }
+
+    #[test]
+    fn test_custom_error_type() {
+        #[derive(Debug, PartialEq)]
+        enum EmbedderError {
+            Lex { message: String, row: usize },
+        }
+
+        impl From<LexerError> for EmbedderError {
+            fn from(error: LexerError) -> Self {
+                EmbedderError::Lex { message: error.message, row: error.span.start.row }
+            }
+        }
+
+        let mut lexer = Lexer::new("x\n§".chars());
+        assert_eq!(lexer.next_token_as::<EmbedderError>(), Ok(Token::symbol("x")));
+        assert_eq!(
+            lexer.next_token_as::<EmbedderError>(),
+            Err(EmbedderError::Lex { message: String::from("Failed to lex source"), row: 1 })
+        );
+        assert_eq!(lexer.next_token_as::<EmbedderError>(), Ok(Token::Eof));
+    }
+
+    #[test]
+    fn test_ends_with_terminator() {
+        for source in &["x = 1;", "fn f() { return 1; }", "while x { }", "x = 1; y = 2;"] {
+            assert!(ends_with_terminator(&lex_source(source)), "{}", source);
+        }
+
+        for source in &["", "x = 1", "fn f() {", "x = f(", "x = 1; y"] {
+            assert!(!ends_with_terminator(&lex_source(source)), "{}", source);
+        }
+
+        assert!(ends_with_terminator(&[Token::Semicolon, Token::Eof]));
+    }
+
+    #[test]
+    fn test_bracket_balance() {
+        let balance = |source: &str| bracket_balance(&lex_source(source));
+
+        assert_eq!(balance("f(a[0], { x: \"(\" }) # ["), BracketBalance::Balanced);
+        assert_eq!(balance(""), BracketBalance::Balanced);
+        assert_eq!(balance("#[inline] fn f() {}"), BracketBalance::Balanced);
+        assert_eq!(balance("#[derive(Clone)"), BracketBalance::Open(1));
+
+        assert_eq!(balance("fn f() {"), BracketBalance::Open(1));
+        assert_eq!(balance("x = [f(1,"), BracketBalance::Open(2));
+
+        assert_eq!(balance("x = 1)"), BracketBalance::Unmatched(3));
+        assert_eq!(balance("f(a]"), BracketBalance::Unmatched(3));
+        assert_eq!(balance(") ("), BracketBalance::Unmatched(0));
+    }
+
+    #[test]
+    fn test_logical_operators_after_keywords() {
         assert_eq!(
-            tokens,
+            lex_source("true && false"),
+            vec![Token::bool(true), Token::LogicalAnd, Token::bool(false)]
+        );
+        assert_eq!(
+            lex_source("true || false"),
+            vec![Token::bool(true), Token::LogicalOr, Token::bool(false)]
+        );
+        assert_eq!(
+            lex_source("true&&false||true"),
             vec![
-                Token::IntType,
-                Token::Symbol { name: String::from("i") },
-                Token::Assignment,
-                Token::IntValue { value: BigInt::from(5) },
-                Token::Semicolon,
-                Token::FloatType,
-                Token::Symbol { name: String::from("f") },
-                Token::Assignment,
-                Token::FloatValue { value: 3.54 },
-                Token::Semicolon,
-                Token::BoolType,
-                Token::Symbol { name: String::from("b") },
-                Token::Assignment,
-                Token::BoolValue { value: true },
-                Token::Semicolon,
-                Token::StringType,
-                Token::Symbol { name: String::from("s1") },
-                Token::Assignment,
-                Token::StringValue { value: String::from("Hello World") },
-                Token::Semicolon,
-                Token::BytesType,
-                Token::Symbol { name: String::from("bb") },
-                Token::Assignment,
-                Token::BytesValue { value: String::from(r#"\x34b"#).as_bytes().to_vec() },
-                Token::Semicolon,
-                Token::ListType,
-                Token::Symbol { name: String::from("l") },
-                Token::Assignment,
-                Token::LeftSquareBracket,
-                Token::Symbol { name: String::from("i") },
-                Token::Comma,
-                Token::Symbol { name: String::from("f") },
-                Token::Comma,
-                Token::Symbol { name: String::from("b") },
-                Token::Comma,
-                Token::Symbol { name: String::from("s1") },
-                Token::Comma,
-                Token::StringValue { value: String::from("some_val") },
-                Token::RightSquareBracket,
-                Token::Semicolon
+                Token::bool(true),
+                Token::LogicalAnd,
+                Token::bool(false),
+                Token::LogicalOr,
+                Token::bool(true),
             ]
         );
     }
 
     #[test]
-    fn test_variable_identifiers() {
-        let source =
-            String::from("str some_str int i float _ff bytes ba char c tuple t list lll dict d enum e");
-        let tokens = lex_source(&source);
+    fn test_trace() {
+        let sink = Rc::new(RefCell::new(Vec::new()));
+        let mut lexer = Lexer::new("x = 5;\n§".chars()).with_trace(sink.clone());
+
+        while lexer.try_next().is_some() {}
+
+        let trace = String::from_utf8(sink.borrow().clone()).unwrap();
         assert_eq!(
-            tokens,
+            trace.lines().collect::<Vec<_>>(),
             vec![
-                Token::StringType,
-                Token::Symbol {
-                    name: String::from("some_str")
-                },
-                Token::IntType,
-                Token::Symbol {
-                    name: String::from("i")
-                },
-                Token::FloatType,
-                Token::Symbol {
-                    name: String::from("_ff")
-                },
-                Token::BytesType,
-                Token::Symbol {
-                    name: String::from("ba")
-                },
-                Token::CharType,
-                Token::Symbol {
-                    name: String::from("c")
-                },
-                Token::TupleType,
-                Token::Symbol {
-                    name: String::from("t")
-                },
-                Token::ListType,
-                Token::Symbol {
-                    name: String::from("lll")
-                },
-                Token::DictType,
-                Token::Symbol {
-                    name: String::from("d")
-                },
-                Token::EnumType,
-                Token::Symbol {
-                    name: String::from("e")
-                },
+                "0:0-0:1 Symbol x",
+                "0:2-0:3 Assignment =",
+                "0:4-0:5 IntValue 5",
+                "0:5-0:6 Semicolon ;",
+                "1:0-1:1 error: Failed to lex source",
             ]
         );
     }
 
     #[test]
-    fn test_string_literal() {
-        let source = String::from(r#""Some string value""#);
-        let tokens = lex_source(&source);
+    fn test_format_strings() {
         assert_eq!(
-            tokens,
-            vec![Token::StringValue {
-                value: String::from("Some string value")
-            },]
+            lex_source("f\"x={x+1}\";"),
+            vec![
+                Token::FormatStringStart,
+                Token::string("x="),
+                Token::FormatHoleStart,
+                Token::symbol("x"),
+                Token::Add,
+                Token::int(1),
+                Token::FormatHoleEnd,
+                Token::FormatStringEnd,
+                Token::Semicolon,
+            ]
         );
-    }
 
-    #[test]
-    fn test_bytes_literal() {
-        let source = String::from(r#"b"hello \x01\03 \x44""#);
-        let tokens = lex_source(&source);
         assert_eq!(
-            tokens,
-            vec![Token::BytesValue {
-                value: String::from(r#"hello \x01\03 \x44"#).as_bytes().to_vec()
-            },]
+            lex_source("f\"{{{a}}}\\n{b[\"}\"]}\""),
+            vec![
+                Token::FormatStringStart,
+                Token::string("{"),
+                Token::FormatHoleStart,
+                Token::symbol("a"),
+                Token::FormatHoleEnd,
+                Token::string("}\n"),
+                Token::FormatHoleStart,
+                Token::symbol("b"),
+                Token::LeftSquareBracket,
+                Token::string("}"),
+                Token::RightSquareBracket,
+                Token::FormatHoleEnd,
+                Token::FormatStringEnd,
+            ]
+        );
+
+        let stream = Lexer::new("s = f\"ab{ c }\"".chars()).lex_with_spans().unwrap();
+        let columns: Vec<(usize, usize)> = stream
+            .iter()
+            .map(|spanned| (spanned.span.start.column, spanned.span.end.column))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![(0, 1), (2, 3), (4, 6), (6, 8), (8, 9), (10, 11), (12, 13), (13, 14)]
         );
+
+        for (source, message) in &[
+            ("f\"{}\"", "Empty expression in format string"),
+            ("f\"a}\"", "Unmatched '}' in format string (use '}}' for a literal one)"),
+            ("f\"{a\"", "Failed to parse format string: unclosed '{'"),
+            ("f\"a", "Failed to parse format string: missing double-quotes"),
+        ] {
+            let error = Lexer::new(source.chars()).next_token().unwrap_err();
+            assert_eq!(&error.message, message, "{}", source);
+        }
+
+        let mut lexer = Lexer::new("f\"a{b}\"".chars()).with_raw_lexemes(true);
+        assert_eq!(lexer.next_spanned_token().unwrap().raw, Some(String::from("f\"")));
+
+        // Chunks are spelled as they'd appear inside the format string
+        for raw in &[false, true] {
+            let mut lexer = Lexer::new("f\"{{a}}\\u{22}\n{b}\"".chars()).with_raw_lexemes(*raw);
+            lexer.next_token().unwrap();
+            let chunk = lexer.next_spanned_token().unwrap();
+            assert_eq!(chunk.token, Token::string("{a}\"\n"));
+            let expected = if *raw { "{{a}}\\u{22}\n" } else { "{{a}}\\\"\\n" };
+            assert_eq!(chunk.to_source(), expected);
+        }
+
+        // Only right before a quote is 'f' a prefix
+        assert_eq!(lex_source("f \"a\""), vec![Token::symbol("f"), Token::string("a")]);
     }
 
     #[test]
-    fn test_character_literal() {
-        let source = String::from("'a'");
-        let tokens = lex_source(&source);
+    fn test_static_accessor_and_angle_brackets() {
+        let foo = || Token::symbol("Foo");
+        let bar = || Token::symbol("Bar");
+
+        assert_eq!(lex_source("Foo::Bar"), vec![foo(), Token::StaticAccessor, bar()]);
         assert_eq!(
-            tokens,
-            vec![Token::CharValue {
-                value: 'a'
-            },]
+            lex_source("Foo::Bar::<int>"),
+            vec![
+                foo(),
+                Token::StaticAccessor,
+                bar(),
+                Token::StaticAccessor,
+                Token::Less,
+                Token::IntType,
+                Token::Greater,
+            ]
+        );
+        assert_eq!(
+            lex_source("a < b > c"),
+            vec![
+                Token::symbol("a"),
+                Token::Less,
+                Token::symbol("b"),
+                Token::Greater,
+                Token::symbol("c"),
+            ]
+        );
+
+        // Nested generics close with a shift, and "::" always takes the first two colons
+        assert_eq!(
+            lex_source("Foo::<list<int>>"),
+            vec![
+                foo(),
+                Token::StaticAccessor,
+                Token::Less,
+                Token::ListType,
+                Token::Less,
+                Token::IntType,
+                Token::BitwiseRightShift,
+            ]
+        );
+        assert_eq!(
+            lex_source("a:::b ::= c"),
+            vec![
+                Token::symbol("a"),
+                Token::StaticAccessor,
+                Token::Colon,
+                Token::symbol("b"),
+                Token::StaticAccessor,
+                Token::Assignment,
+                Token::symbol("c"),
+            ]
         );
     }
 
     #[test]
-    fn test_number_literal() {
-        let source = String::from("423 763.433 0 24454333");
-        let tokens = lex_source(&source);
+    fn test_form_feed_and_vertical_tab() {
+        let stream = Lexer::new("a\u{c}b \u{b}c\n\u{c}d".chars()).lex_with_spans().unwrap();
+        let starts: Vec<Position> = stream.iter().map(|spanned| spanned.span.start).collect();
         assert_eq!(
-            tokens,
+            starts,
             vec![
-                Token::IntValue {
-                    value: BigInt::from(423)
-                },
-                Token::FloatValue { value: 763.433 },
-                Token::IntValue {
-                    value: BigInt::from(0)
-                },
-                Token::IntValue {
-                    value: BigInt::from(24454333)
-                },
+                Position { row: 0, column: 0 },
+                Position { row: 0, column: 1 },
+                Position { row: 0, column: 3 },
+                Position { row: 1, column: 0 },
             ]
         );
+
+        // Nor do they indent the line they start
+        let mut lexer = Lexer::new("a\n\u{c}b\n  \u{c}c".chars()).with_layout_mode(true);
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("a"));
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("b"));
+        assert_eq!(lexer.next_token().unwrap(), Token::Indent);
+        assert_eq!(lexer.next_token().unwrap(), Token::symbol("c"));
     }
 
     #[test]
-    fn test_operators() {
-        let source = String::from("|| && + - * / % | ^ ~ & >> << ! == != > >= < <= =");
-        let tokens = lex_source(&source);
+    fn test_adjacent_strings_across_statement_newlines() {
+        let lex = |source: &str, terminator: StatementTerminator| -> Vec<Token> {
+            let lexer = Lexer::new(source.chars())
+                .with_adjacent_string_concatenation(true)
+                .with_statement_terminator(terminator);
+            lexer.tokenize().unwrap()
+        };
+        let x = || Token::symbol("x");
+
         assert_eq!(
-            tokens,
+            lex("x = \"a\"\ny", StatementTerminator::Automatic),
             vec![
-                Token::LogicalOr,
-                Token::LogicalAnd,
-                Token::Add,
-                Token::Subtract,
-                Token::Multiply,
-                Token::Divide,
-                Token::Modulo,
-                Token::BitwiseOr,
-                Token::BitwiseXor,
-                Token::BitwiseNot,
-                Token::BitwiseAnd,
-                Token::BitwiseRightShift,
-                Token::BitwiseLeftShift,
-                Token::Not,
-                Token::Equals,
-                Token::NotEquals,
-                Token::Greater,
-                Token::GreaterEqual,
-                Token::Less,
-                Token::LessEqual,
-                Token::Assignment
+                x(),
+                Token::Assignment,
+                Token::string("a"),
+                Token::Semicolon,
+                Token::symbol("y"),
+                Token::Semicolon,
+            ]
+        );
+        assert_eq!(
+            lex("x = \"a\"\n\"b\"", StatementTerminator::Newline),
+            vec![
+                x(),
+                Token::Assignment,
+                Token::string("a"),
+                Token::Semicolon,
+                Token::string("b"),
+                Token::Semicolon,
             ]
         );
+
+        // Strings on the same line are still joined, and so are continued lines
+        assert_eq!(
+            lex("x = \"a\" # c\n", StatementTerminator::Newline),
+            lex("x = \"a\"\n", StatementTerminator::Newline)
+        );
+        assert_eq!(
+            lex("x = \"a\" \"b\" \\\n \"c\"\n", StatementTerminator::Automatic),
+            vec![x(), Token::Assignment, Token::string("abc"), Token::Semicolon]
+        );
+
+        assert_eq!(
+            lex("x = \"a\"\r\n\"b\";", StatementTerminator::Semicolon),
+            vec![x(), Token::Assignment, Token::string("ab"), Token::Semicolon]
+        );
     }
 
     #[test]
-    fn test_delimiters() {
-        let source = String::from("( ) { } [ ] . ; , :: ->");
-        let tokens = lex_source(&source);
+    fn test_format_string_options() {
+        let error = |lexer: Lexer<std::str::Chars>| lexer.tokenize().unwrap_err().message;
+
+        let source = "f\"{a\u{a0}b}\"";
+        assert!(Lexer::new(source.chars()).tokenize().is_ok());
         assert_eq!(
-            tokens,
+            error(Lexer::new(source.chars()).with_ascii_whitespace(true)),
+            "Unexpected non-ASCII whitespace"
+        );
+
+        let source = "f\"{abcdefgh}\"";
+        assert!(Lexer::new(source.chars()).tokenize().is_ok());
+        assert_eq!(
+            error(Lexer::new(source.chars()).with_max_identifier_length(3)),
+            "Identifier is 8 chars long, more than the maximum of 3"
+        );
+
+        for source in &["f\"a\u{fffd}\"", "f\"{\"\u{fffd}\"}\""] {
+            assert!(Lexer::new(source.chars()).tokenize().is_ok(), "{}", source);
+            assert_eq!(
+                error(Lexer::new(source.chars()).with_replacement_char_rejection(true)),
+                REPLACEMENT_CHAR_ERROR
+            );
+        }
+
+        let lexer = Lexer::new("f\"{IF}\"".chars()).with_case_insensitive_keywords(true);
+        assert_eq!(lexer.tokenize().unwrap()[2], Token::If);
+
+        // Holes never get semicolons of their own
+        let lexer = Lexer::new("f\"{a}\"\n".chars())
+            .with_statement_terminator(StatementTerminator::Automatic);
+        assert_eq!(
+            lexer.tokenize().unwrap(),
             vec![
-                Token::LeftParens,
-                Token::RightParens,
-                Token::LeftCurlyBracket,
-                Token::RightCurlyBracket,
-                Token::LeftSquareBracket,
-                Token::RightSquareBracket,
-                Token::MemberAccessor,
+                Token::FormatStringStart,
+                Token::FormatHoleStart,
+                Token::symbol("a"),
+                Token::FormatHoleEnd,
+                Token::FormatStringEnd,
                 Token::Semicolon,
-                Token::Comma,
-                Token::StaticAccessor,
-                Token::FnReturnTypeDelim
             ]
         );
     }
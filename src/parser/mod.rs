@@ -1,4 +1,7 @@
 pub mod errors;
 pub mod token;
 pub mod lexer;
+pub mod reader;
+pub mod span;
+pub mod stream;
 
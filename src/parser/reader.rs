@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+use std::io::{BufRead, Bytes};
+use std::rc::Rc;
+
+// Shared between a `ReaderChars` and the lexer consuming it, since a plain char iterator has no
+// way of reporting why it stopped.
+pub type SourceError = Rc<RefCell<Option<String>>>;
+
+// Decodes UTF-8 chars out of a `BufRead` one at a time. Iteration stops at the first IO or
+// decoding error, which is then kept in the `SourceError` returned by `error()`.
+pub struct ReaderChars<R: BufRead> {
+    bytes: Bytes<R>,
+    error: SourceError,
+}
+
+impl<R: BufRead> ReaderChars<R> {
+    pub fn new(reader: R) -> Self {
+        ReaderChars {
+            bytes: reader.bytes(),
+            error: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn error(&self) -> SourceError {
+        Rc::clone(&self.error)
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        match self.bytes.next() {
+            Some(Ok(byte)) => Some(byte),
+            Some(Err(err)) => {
+                self.fail(format!("Failed to read source: {}", err));
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn fail(&mut self, message: String) {
+        *self.error.borrow_mut() = Some(message);
+    }
+}
+
+impl<R: BufRead> Iterator for ReaderChars<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.error.borrow().is_some() {
+            return None;
+        }
+
+        let first = self.next_byte()?;
+        let length = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => {
+                self.fail(String::from("Failed to decode source: invalid UTF-8"));
+                return None;
+            }
+        };
+
+        let mut encoded = vec![first];
+
+        while encoded.len() < length {
+            match self.next_byte() {
+                Some(byte) => encoded.push(byte),
+                None => {
+                    if self.error.borrow().is_none() {
+                        self.fail(String::from("Failed to decode source: truncated UTF-8 sequence"));
+                    }
+                    return None;
+                }
+            }
+        }
+
+        match std::str::from_utf8(&encoded) {
+            Ok(decoded) => decoded.chars().next(),
+            Err(_) => {
+                self.fail(String::from("Failed to decode source: invalid UTF-8"));
+                None
+            }
+        }
+    }
+}
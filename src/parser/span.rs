@@ -0,0 +1,44 @@
+use crate::parser::token::Token;
+
+// Rows and columns are both zero-based. Positions order by row, then column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub row: usize,
+    pub column: usize,
+}
+
+// Covers the characters from `start` up to, but not including, `end`. Spans order by where they
+// start (and then by where they end), i.e: in source order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    // The exact source text of the token, when the lexer was asked to preserve it. Format string
+    // chunks always have one, since they're spelled without the quotes of a `StringValue`.
+    pub raw: Option<String>,
+    // Comments and blank lines skipped right before the token, when the lexer was asked to keep them.
+    pub leading_trivia: Vec<Trivia>,
+}
+
+impl SpannedToken {
+    // The token's source text as written when it was kept, or else its canonical spelling.
+    pub fn to_source(&self) -> String {
+        self.raw.clone().unwrap_or_else(|| self.token.to_source())
+    }
+}
+
+// Source text that isn't part of any token, kept around for tools (e.g: formatters) that have to
+// reproduce it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trivia {
+    // The text of a `#` comment, without the `#` itself.
+    Comment(String),
+    // A run of consecutive empty (or whitespace only) lines.
+    BlankLines(usize),
+}
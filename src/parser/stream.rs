@@ -0,0 +1,183 @@
+use crate::parser::span::{Span, SpannedToken};
+use crate::parser::token::{Token, TokenKind};
+use std::ops::Index;
+use std::slice::Iter;
+
+// The lexed tokens of a whole source, for parsers to index into.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenStream {
+    tokens: Vec<SpannedToken>,
+}
+
+impl TokenStream {
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
+        TokenStream { tokens }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_, SpannedToken> {
+        self.tokens.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SpannedToken> {
+        self.tokens.get(index)
+    }
+
+    // Like `get`, without the span.
+    pub fn peek_at(&self, index: usize) -> Option<&Token> {
+        self.tokens.get(index).map(|spanned| &spanned.token)
+    }
+
+    // Compares the kinds of the tokens (ignoring their payloads) with `kinds`, returning the index of
+    // the first one that differs. When one is a prefix of the other, they diverge where it ends.
+    pub fn expect_sequence(&self, kinds: &[TokenKind]) -> Result<(), usize> {
+        let matching = self
+            .tokens
+            .iter()
+            .zip(kinds)
+            .take_while(|(spanned, kind)| spanned.token.kind() == **kind)
+            .count();
+
+        if matching == self.tokens.len() && matching == kinds.len() {
+            Ok(())
+        } else {
+            Err(matching)
+        }
+    }
+
+    // Spans of the commas directly followed by a closing bracket (i.e: the last one in `[1, 2,]`),
+    // for formatters that care whether a trailing comma was written.
+    pub fn trailing_commas(&self) -> Vec<Span> {
+        self.tokens
+            .windows(2)
+            .filter(|pair| {
+                pair[0].token == Token::Comma
+                    && matches!(
+                        pair[1].token,
+                        Token::RightParens | Token::RightSquareBracket | Token::RightCurlyBracket
+                    )
+            })
+            .map(|pair| pair[0].span)
+            .collect()
+    }
+
+    pub fn into_inner(self) -> Vec<SpannedToken> {
+        self.tokens
+    }
+}
+
+impl Index<usize> for TokenStream {
+    type Output = SpannedToken;
+
+    fn index(&self, index: usize) -> &SpannedToken {
+        &self.tokens[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a TokenStream {
+    type Item = &'a SpannedToken;
+    type IntoIter = Iter<'a, SpannedToken>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tokens.iter()
+    }
+}
+
+impl IntoIterator for TokenStream {
+    type Item = SpannedToken;
+    type IntoIter = std::vec::IntoIter<SpannedToken>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tokens.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::lexer::Lexer;
+    use crate::parser::span::{Position, Span};
+    use crate::parser::token::{Token, TokenKind};
+
+    #[test]
+    fn test_indexing() {
+        let stream = Lexer::new("x = 5;".chars()).lex_with_spans().unwrap();
+
+        assert_eq!(stream.len(), 4);
+        assert!(!stream.is_empty());
+        assert_eq!(stream[0].token, Token::symbol("x"));
+        assert_eq!(stream[2].span.start, Position { row: 0, column: 4 });
+        assert_eq!(stream.get(3).unwrap().token, Token::Semicolon);
+        assert_eq!(stream.get(4), None);
+        assert_eq!(stream.peek_at(1), Some(&Token::Assignment));
+        assert_eq!(stream.peek_at(4), None);
+    }
+
+    #[test]
+    fn test_iteration() {
+        let stream = Lexer::new("a + b".chars()).lex_with_spans().unwrap();
+
+        let tokens: Vec<&Token> = stream.iter().map(|spanned| &spanned.token).collect();
+        assert_eq!(tokens, vec![&Token::symbol("a"), &Token::Add, &Token::symbol("b")]);
+
+        let mut columns: Vec<usize> = Vec::new();
+        for spanned in &stream {
+            columns.push(spanned.span.start.column);
+        }
+        assert_eq!(columns, vec![0, 2, 4]);
+
+        let tokens: Vec<Token> = stream.into_iter().map(|spanned| spanned.token).collect();
+        assert_eq!(tokens, vec![Token::symbol("a"), Token::Add, Token::symbol("b")]);
+
+        assert!(Lexer::new("".chars()).lex_with_spans().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_expect_sequence() {
+        let stream = Lexer::new("x = f(1);".chars()).lex_with_spans().unwrap();
+        let kinds = [
+            TokenKind::Symbol,
+            TokenKind::Assignment,
+            TokenKind::Symbol,
+            TokenKind::LeftParens,
+            TokenKind::IntValue,
+            TokenKind::RightParens,
+            TokenKind::Semicolon,
+        ];
+
+        assert_eq!(stream.expect_sequence(&kinds), Ok(()));
+        assert_eq!(stream.expect_sequence(&kinds[..6]), Err(6));
+        assert_eq!(stream.expect_sequence(&[TokenKind::Symbol, TokenKind::Walrus]), Err(1));
+        assert_eq!(stream.expect_sequence(&[]), Err(0));
+        assert_eq!(Lexer::new("".chars()).lex_with_spans().unwrap().expect_sequence(&[]), Ok(()));
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let stream = Lexer::new("[1, 2,]".chars()).lex_with_spans().unwrap();
+        assert_eq!(
+            stream.trailing_commas(),
+            vec![Span {
+                start: Position { row: 0, column: 5 },
+                end: Position { row: 0, column: 6 },
+            }]
+        );
+
+        let stream = Lexer::new("f(a, b) { x,\n}".chars()).lex_with_spans().unwrap();
+        assert_eq!(
+            stream.trailing_commas(),
+            vec![Span {
+                start: Position { row: 0, column: 11 },
+                end: Position { row: 0, column: 12 },
+            }]
+        );
+
+        assert!(Lexer::new("[1, 2]".chars()).lex_with_spans().unwrap().trailing_commas().is_empty());
+    }
+}
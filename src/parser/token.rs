@@ -12,9 +12,30 @@ pub enum Token {
     RightSquareBracket, // ']'
     Semicolon, // ';'
     StaticAccessor, // '::'
+    Colon, // ':'
+    Walrus, // ':='
     MemberAccessor, // '.'
+    RangeExclusive, // '..'
+    Ellipsis, // '...'
     FnReturnTypeDelim, // '->'
+    FatArrow, // '=>'
     Comma, // ','
+    HashBracket, // '#['
+    Underscore, // '_' on its own (i.e: a wildcard pattern)
+    Dollar, // '$'
+    // Format strings (i.e: f"x={x}") come as literal chunks (`StringValue`s) and the tokens of
+    // each hole's expression, between these
+    FormatStringStart, // 'f"'
+    FormatStringEnd, // '"'
+    FormatHoleStart, // '{'
+    FormatHoleEnd, // '}'
+
+    // Layout (only emitted in layout mode)
+    Indent,
+    Dedent,
+
+    // End of input
+    Eof,
 
     Symbol { name: String }, 
 
@@ -23,6 +44,8 @@ pub enum Token {
     IntValue { value: BigInt },
     FloatType,
     FloatValue { value: f64 },
+    // Exact decimal literal (i.e: 1.50d), kept as written for an arbitrary-precision type
+    DecimalValue { value: String },
     StringType,
     StringValue { value: String },
     CharType,
@@ -43,6 +66,496 @@ pub enum Token {
     Else,
     For,
     While,
+    Unless,
+    Until,
+    When,
+    Defer,
+    Class,
+    Function,
+    Private,
+    Public,
+    NewInstance,
+    SelfInstance,
+    Constructor,
+    Destructor,
+    Super,
+    Virtual,
+    Override,
+    Abstract,
+    Static,
+    Global,
+    Nonlocal,
+    Return,
+    DelObject,
+    Assert,
+    Yield,
+    Typeof,
+    Sizeof,
+
+    // Operators
+    LogicalOr,
+    LogicalAnd,
+    Add,
+    Subtract,
+    Multiply,
+    Power,
+    Divide,
+    Modulo,
+    BitwiseOr,
+    PipeForward,
+    BitwiseXor,
+    BitwiseNot,
+    BitwiseAnd,
+    BitwiseRightShift,
+    BitwiseLeftShift,
+    Not,
+    Equals,
+    NotEquals,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Question,
+    NullCoalesce,
+    QuestionDot,
+    Assignment,
+    AddAssign,
+    SubtractAssign,
+    MultiplyAssign,
+    DivideAssign,
+    ModuloAssign,
+    PowAssign,
+}
+
+impl Token {
+    // Shorthands for building payload-bearing tokens (e.g: `Token::symbol("x")`).
+
+    pub fn symbol(name: &str) -> Token {
+        Token::Symbol { name: String::from(name) }
+    }
+
+    pub fn int<V: Into<BigInt>>(value: V) -> Token {
+        Token::IntValue { value: value.into() }
+    }
+
+    pub fn float(value: f64) -> Token {
+        Token::FloatValue { value }
+    }
+
+    pub fn string(value: &str) -> Token {
+        Token::StringValue { value: String::from(value) }
+    }
+
+    pub fn char(value: char) -> Token {
+        Token::CharValue { value }
+    }
+
+    pub fn bool(value: bool) -> Token {
+        Token::BoolValue { value }
+    }
+
+    pub fn bytes(value: &[u8]) -> Token {
+        Token::BytesValue { value: value.to_vec() }
+    }
+
+    // Source text that lexes back to this token: the canonical spelling of keywords and operators,
+    // and literals written out in full (i.e: `IntValue` in decimal, even if it was lexed from hex).
+    // Symbols named like a keyword need `r#` to round-trip, which only their raw lexeme has.
+    pub fn to_source(&self) -> String {
+        let source = match self {
+            Token::LeftParens => "(",
+            Token::RightParens => ")",
+            Token::LeftCurlyBracket => "{",
+            Token::RightCurlyBracket => "}",
+            Token::LeftSquareBracket => "[",
+            Token::RightSquareBracket => "]",
+            Token::Semicolon => ";",
+            Token::StaticAccessor => "::",
+            Token::Colon => ":",
+            Token::Walrus => ":=",
+            Token::MemberAccessor => ".",
+            Token::RangeExclusive => "..",
+            Token::Ellipsis => "...",
+            Token::FnReturnTypeDelim => "->",
+            Token::FatArrow => "=>",
+            Token::Comma => ",",
+            Token::HashBracket => "#[",
+            Token::Underscore => "_",
+            Token::Dollar => "$",
+            Token::FormatStringStart => "f\"",
+            Token::FormatStringEnd => "\"",
+            Token::FormatHoleStart => "{",
+            Token::FormatHoleEnd => "}",
+            Token::Indent | Token::Dedent | Token::Eof => "",
+            Token::Symbol { name } => return name.clone(),
+            Token::IntType => "int",
+            Token::IntValue { value } => return value.to_string(),
+            Token::FloatType => "float",
+            Token::FloatValue { value } => {
+                // Whole floats still need a fraction, or they'd lex back as ints
+                let float = value.to_string();
+                return if float.contains('.') { float } else { format!("{}.0", float) };
+            }
+            Token::DecimalValue { value } => return format!("{}d", value),
+            Token::StringType => "str",
+            Token::StringValue { value } => {
+                let string: String = value.chars().map(|chr| escape(chr, '"')).collect();
+                return format!("\"{}\"", string);
+            }
+            Token::CharType => "char",
+            Token::CharValue { value } => return format!("'{}'", escape(*value, '\'')),
+            Token::BoolType => "bool",
+            Token::BoolValue { value: true } => "true",
+            Token::BoolValue { value: false } => "false",
+            Token::BytesType => "bytes",
+            Token::BytesValue { value } => {
+                let bytes: String = value
+                    .iter()
+                    .map(|&byte| match byte {
+                        b'"' | b'\\' => format!("\\{}", byte as char),
+                        b' '..=b'~' => (byte as char).to_string(),
+                        _ => format!("\\x{:02x}", byte),
+                    })
+                    .collect();
+                return format!("b\"{}\"", bytes);
+            }
+            Token::TupleType => "tuple",
+            Token::EnumType => "enum",
+            Token::ListType => "list",
+            Token::DictType => "dict",
+            Token::NullValue => "null",
+            Token::If => "if",
+            Token::Elif => "elif",
+            Token::Else => "else",
+            Token::For => "for",
+            Token::While => "while",
+            Token::Unless => "unless",
+            Token::Until => "until",
+            Token::When => "when",
+            Token::Defer => "defer",
+            Token::Class => "class",
+            Token::Function => "fn",
+            Token::Private => "priv",
+            Token::Public => "pub",
+            Token::NewInstance => "new",
+            Token::SelfInstance => "self",
+            Token::Constructor => "construct",
+            Token::Destructor => "destruct",
+            Token::Super => "super",
+            Token::Virtual => "virtual",
+            Token::Override => "override",
+            Token::Abstract => "abstract",
+            Token::Static => "static",
+            Token::Global => "global",
+            Token::Nonlocal => "nonlocal",
+            Token::Return => "return",
+            Token::DelObject => "del",
+            Token::Assert => "assert",
+            Token::Yield => "yield",
+            Token::Typeof => "typeof",
+            Token::Sizeof => "sizeof",
+            Token::LogicalOr => "||",
+            Token::LogicalAnd => "&&",
+            Token::Add => "+",
+            Token::Subtract => "-",
+            Token::Multiply => "*",
+            Token::Power => "**",
+            Token::Divide => "/",
+            Token::Modulo => "%",
+            Token::BitwiseOr => "|",
+            Token::PipeForward => "|>",
+            Token::BitwiseXor => "^",
+            Token::BitwiseNot => "~",
+            Token::BitwiseAnd => "&",
+            Token::BitwiseRightShift => ">>",
+            Token::BitwiseLeftShift => "<<",
+            Token::Not => "!",
+            Token::Equals => "==",
+            Token::NotEquals => "!=",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Question => "?",
+            Token::NullCoalesce => "??",
+            Token::QuestionDot => "?.",
+            Token::Assignment => "=",
+            Token::AddAssign => "+=",
+            Token::SubtractAssign => "-=",
+            Token::MultiplyAssign => "*=",
+            Token::DivideAssign => "/=",
+            Token::ModuloAssign => "%=",
+            Token::PowAssign => "**=",
+        };
+
+        String::from(source)
+    }
+
+    // Whether the token is one of the builtin type keywords (i.e: `int`, `list`).
+    pub fn is_type(&self) -> bool {
+        matches!(
+            self,
+            Token::IntType
+                | Token::FloatType
+                | Token::StringType
+                | Token::CharType
+                | Token::BoolType
+                | Token::BytesType
+                | Token::TupleType
+                | Token::EnumType
+                | Token::ListType
+                | Token::DictType
+        )
+    }
+
+    // Binding power of the token when used as a binary operator (higher binds tighter), or `None`
+    // when it isn't one.
+    pub fn binary_precedence(&self) -> Option<u8> {
+        match self {
+            Token::Assignment
+            | Token::AddAssign
+            | Token::SubtractAssign
+            | Token::MultiplyAssign
+            | Token::DivideAssign
+            | Token::ModuloAssign
+            | Token::PowAssign
+            | Token::Walrus => Some(1),
+            Token::PipeForward => Some(2),
+            Token::RangeExclusive => Some(3),
+            Token::NullCoalesce => Some(4),
+            Token::LogicalOr => Some(5),
+            Token::LogicalAnd => Some(6),
+            Token::Equals
+            | Token::NotEquals
+            | Token::Greater
+            | Token::GreaterEqual
+            | Token::Less
+            | Token::LessEqual => Some(7),
+            Token::BitwiseOr => Some(8),
+            Token::BitwiseXor => Some(9),
+            Token::BitwiseAnd => Some(10),
+            Token::BitwiseLeftShift | Token::BitwiseRightShift => Some(11),
+            Token::Add | Token::Subtract => Some(12),
+            Token::Multiply | Token::Divide | Token::Modulo => Some(13),
+            Token::Power => Some(14),
+            _ => None,
+        }
+    }
+
+    // Whether `a op b op c` groups as `a op (b op c)` rather than `(a op b) op c`.
+    pub fn is_right_associative(&self) -> bool {
+        matches!(
+            self,
+            Token::Power
+                | Token::Assignment
+                | Token::AddAssign
+                | Token::SubtractAssign
+                | Token::MultiplyAssign
+                | Token::DivideAssign
+                | Token::ModuloAssign
+                | Token::PowAssign
+                | Token::Walrus
+        )
+    }
+
+    // Returns the payload-free kind of this token, which is cheap to compare and copy.
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::LeftParens => TokenKind::LeftParens,
+            Token::RightParens => TokenKind::RightParens,
+            Token::LeftCurlyBracket => TokenKind::LeftCurlyBracket,
+            Token::RightCurlyBracket => TokenKind::RightCurlyBracket,
+            Token::LeftSquareBracket => TokenKind::LeftSquareBracket,
+            Token::RightSquareBracket => TokenKind::RightSquareBracket,
+            Token::Semicolon => TokenKind::Semicolon,
+            Token::StaticAccessor => TokenKind::StaticAccessor,
+            Token::Colon => TokenKind::Colon,
+            Token::Walrus => TokenKind::Walrus,
+            Token::MemberAccessor => TokenKind::MemberAccessor,
+            Token::RangeExclusive => TokenKind::RangeExclusive,
+            Token::Ellipsis => TokenKind::Ellipsis,
+            Token::FnReturnTypeDelim => TokenKind::FnReturnTypeDelim,
+            Token::FatArrow => TokenKind::FatArrow,
+            Token::Comma => TokenKind::Comma,
+            Token::HashBracket => TokenKind::HashBracket,
+            Token::Underscore => TokenKind::Underscore,
+            Token::Dollar => TokenKind::Dollar,
+            Token::FormatStringStart => TokenKind::FormatStringStart,
+            Token::FormatStringEnd => TokenKind::FormatStringEnd,
+            Token::FormatHoleStart => TokenKind::FormatHoleStart,
+            Token::FormatHoleEnd => TokenKind::FormatHoleEnd,
+            Token::Indent => TokenKind::Indent,
+            Token::Dedent => TokenKind::Dedent,
+            Token::Eof => TokenKind::Eof,
+            Token::Symbol { .. } => TokenKind::Symbol,
+            Token::IntType => TokenKind::IntType,
+            Token::IntValue { .. } => TokenKind::IntValue,
+            Token::FloatType => TokenKind::FloatType,
+            Token::FloatValue { .. } => TokenKind::FloatValue,
+            Token::DecimalValue { .. } => TokenKind::DecimalValue,
+            Token::StringType => TokenKind::StringType,
+            Token::StringValue { .. } => TokenKind::StringValue,
+            Token::CharType => TokenKind::CharType,
+            Token::CharValue { .. } => TokenKind::CharValue,
+            Token::BoolType => TokenKind::BoolType,
+            Token::BoolValue { .. } => TokenKind::BoolValue,
+            Token::BytesType => TokenKind::BytesType,
+            Token::BytesValue { .. } => TokenKind::BytesValue,
+            Token::TupleType => TokenKind::TupleType,
+            Token::EnumType => TokenKind::EnumType,
+            Token::ListType => TokenKind::ListType,
+            Token::DictType => TokenKind::DictType,
+            Token::NullValue => TokenKind::NullValue,
+            Token::If => TokenKind::If,
+            Token::Elif => TokenKind::Elif,
+            Token::Else => TokenKind::Else,
+            Token::For => TokenKind::For,
+            Token::While => TokenKind::While,
+            Token::Unless => TokenKind::Unless,
+            Token::Until => TokenKind::Until,
+            Token::When => TokenKind::When,
+            Token::Defer => TokenKind::Defer,
+            Token::Class => TokenKind::Class,
+            Token::Function => TokenKind::Function,
+            Token::Private => TokenKind::Private,
+            Token::Public => TokenKind::Public,
+            Token::NewInstance => TokenKind::NewInstance,
+            Token::SelfInstance => TokenKind::SelfInstance,
+            Token::Constructor => TokenKind::Constructor,
+            Token::Destructor => TokenKind::Destructor,
+            Token::Super => TokenKind::Super,
+            Token::Virtual => TokenKind::Virtual,
+            Token::Override => TokenKind::Override,
+            Token::Abstract => TokenKind::Abstract,
+            Token::Static => TokenKind::Static,
+            Token::Global => TokenKind::Global,
+            Token::Nonlocal => TokenKind::Nonlocal,
+            Token::Return => TokenKind::Return,
+            Token::DelObject => TokenKind::DelObject,
+            Token::Assert => TokenKind::Assert,
+            Token::Yield => TokenKind::Yield,
+            Token::Typeof => TokenKind::Typeof,
+            Token::Sizeof => TokenKind::Sizeof,
+            Token::LogicalOr => TokenKind::LogicalOr,
+            Token::LogicalAnd => TokenKind::LogicalAnd,
+            Token::Add => TokenKind::Add,
+            Token::Subtract => TokenKind::Subtract,
+            Token::Multiply => TokenKind::Multiply,
+            Token::Power => TokenKind::Power,
+            Token::Divide => TokenKind::Divide,
+            Token::Modulo => TokenKind::Modulo,
+            Token::BitwiseOr => TokenKind::BitwiseOr,
+            Token::PipeForward => TokenKind::PipeForward,
+            Token::BitwiseXor => TokenKind::BitwiseXor,
+            Token::BitwiseNot => TokenKind::BitwiseNot,
+            Token::BitwiseAnd => TokenKind::BitwiseAnd,
+            Token::BitwiseRightShift => TokenKind::BitwiseRightShift,
+            Token::BitwiseLeftShift => TokenKind::BitwiseLeftShift,
+            Token::Not => TokenKind::Not,
+            Token::Equals => TokenKind::Equals,
+            Token::NotEquals => TokenKind::NotEquals,
+            Token::Greater => TokenKind::Greater,
+            Token::GreaterEqual => TokenKind::GreaterEqual,
+            Token::Less => TokenKind::Less,
+            Token::LessEqual => TokenKind::LessEqual,
+            Token::Question => TokenKind::Question,
+            Token::NullCoalesce => TokenKind::NullCoalesce,
+            Token::QuestionDot => TokenKind::QuestionDot,
+            Token::Assignment => TokenKind::Assignment,
+            Token::AddAssign => TokenKind::AddAssign,
+            Token::SubtractAssign => TokenKind::SubtractAssign,
+            Token::MultiplyAssign => TokenKind::MultiplyAssign,
+            Token::DivideAssign => TokenKind::DivideAssign,
+            Token::ModuloAssign => TokenKind::ModuloAssign,
+            Token::PowAssign => TokenKind::PowAssign,
+        }
+    }
+}
+
+// Escapes `chr` for use inside a string or char literal delimited by `quote`.
+pub(crate) fn escape(chr: char, quote: char) -> String {
+    match chr {
+        '\n' => String::from("\\n"),
+        '\r' => String::from("\\r"),
+        '\t' => String::from("\\t"),
+        '\0' => String::from("\\0"),
+        '\\' => String::from("\\\\"),
+        _ if chr == quote => format!("\\{}", quote),
+        _ => chr.to_string(),
+    }
+}
+
+// Discriminant of a `Token`, without any of its payload values. Unlike `Token` (whose
+// `FloatValue` payload rules out `Eq`), kinds can be used as map keys. Kinds order by where they're
+// declared below, so sorted output stays deterministic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TokenKind {
+    // Specials characters
+    LeftParens, // '('
+    RightParens, // ')'
+    LeftCurlyBracket, // '{'
+    RightCurlyBracket, // '}'
+    LeftSquareBracket, // '['
+    RightSquareBracket, // ']'
+    Semicolon, // ';'
+    StaticAccessor, // '::'
+    Colon, // ':'
+    Walrus, // ':='
+    MemberAccessor, // '.'
+    RangeExclusive, // '..'
+    Ellipsis, // '...'
+    FnReturnTypeDelim, // '->'
+    FatArrow, // '=>'
+    Comma, // ','
+    HashBracket, // '#['
+    Underscore, // '_' on its own (i.e: a wildcard pattern)
+    Dollar, // '$'
+    // Format strings (i.e: f"x={x}") come as literal chunks (`StringValue`s) and the tokens of
+    // each hole's expression, between these
+    FormatStringStart, // 'f"'
+    FormatStringEnd, // '"'
+    FormatHoleStart, // '{'
+    FormatHoleEnd, // '}'
+
+    // Layout (only emitted in layout mode)
+    Indent,
+    Dedent,
+
+    // End of input
+    Eof,
+
+    Symbol,
+
+    // Builtin types
+    IntType,
+    IntValue,
+    FloatType,
+    FloatValue,
+    DecimalValue,
+    StringType,
+    StringValue,
+    CharType,
+    CharValue,
+    BoolType,
+    BoolValue,
+    BytesType,
+    BytesValue,
+    TupleType,
+    EnumType,
+    ListType,
+    DictType,
+    NullValue,
+
+    // Keywords
+    If,
+    Elif,
+    Else,
+    For,
+    While,
+    Unless,
+    Until,
+    When,
+    Defer,
     Class,
     Function,
     Private,
@@ -52,8 +565,18 @@ pub enum Token {
     Constructor,
     Destructor,
     Super,
+    Virtual,
+    Override,
+    Abstract,
+    Static,
+    Global,
+    Nonlocal,
     Return,
     DelObject,
+    Assert,
+    Yield,
+    Typeof,
+    Sizeof,
 
     // Operators
     LogicalOr,
@@ -61,9 +584,11 @@ pub enum Token {
     Add,
     Subtract,
     Multiply,
+    Power,
     Divide,
     Modulo,
     BitwiseOr,
+    PipeForward,
     BitwiseXor,
     BitwiseNot,
     BitwiseAnd,
@@ -76,5 +601,126 @@ pub enum Token {
     GreaterEqual,
     Less,
     LessEqual,
+    Question,
+    NullCoalesce,
+    QuestionDot,
     Assignment,
-}
\ No newline at end of file
+    AddAssign,
+    SubtractAssign,
+    MultiplyAssign,
+    DivideAssign,
+    ModuloAssign,
+    PowAssign,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::token::{Token, TokenKind};
+    use num_bigint::BigInt;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(Token::LeftParens.kind(), TokenKind::LeftParens);
+        assert_eq!(Token::Class.kind(), TokenKind::Class);
+        assert_eq!(Token::LogicalAnd.kind(), TokenKind::LogicalAnd);
+        assert_eq!(Token::Symbol { name: String::from("foo") }.kind(), TokenKind::Symbol);
+        assert_eq!(Token::IntValue { value: BigInt::from(3) }.kind(), TokenKind::IntValue);
+        assert_eq!(Token::BytesValue { value: vec![1, 2] }.kind(), TokenKind::BytesValue);
+    }
+
+    #[test]
+    fn test_kind_ignores_payload() {
+        assert_eq!(
+            Token::Symbol { name: String::from("foo") }.kind(),
+            Token::Symbol { name: String::from("bar") }.kind()
+        );
+        assert_eq!(
+            Token::IntValue { value: BigInt::from(1) }.kind(),
+            Token::IntValue { value: BigInt::from(2) }.kind()
+        );
+        assert_eq!(
+            Token::FloatValue { value: 1.5 }.kind(),
+            Token::FloatValue { value: -7.0 }.kind()
+        );
+        assert_ne!(
+            Token::StringValue { value: String::from("x") }.kind(),
+            Token::CharValue { value: 'x' }.kind()
+        );
+    }
+
+    #[test]
+    fn test_kind_as_map_key() {
+        let tokens = vec![
+            Token::Symbol { name: String::from("a") },
+            Token::Assignment,
+            Token::Symbol { name: String::from("b") },
+            Token::Add,
+            Token::FloatValue { value: 1.5 },
+        ];
+        let mut counts: HashMap<TokenKind, usize> = HashMap::new();
+
+        for token in &tokens {
+            *counts.entry(token.kind()).or_insert(0) += 1;
+        }
+
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts[&TokenKind::Symbol], 2);
+        assert_eq!(counts[&TokenKind::Assignment], 1);
+        assert_eq!(counts[&TokenKind::FloatValue], 1);
+        assert_eq!(counts.get(&TokenKind::Subtract), None);
+    }
+
+    #[test]
+    fn test_constructors() {
+        assert_eq!(Token::symbol("x"), Token::Symbol { name: String::from("x") });
+        assert_eq!(Token::int(5), Token::IntValue { value: BigInt::from(5) });
+        assert_eq!(Token::int(BigInt::from(7)), Token::IntValue { value: BigInt::from(7) });
+        assert_eq!(Token::float(1.5), Token::FloatValue { value: 1.5 });
+        assert_eq!(Token::string("hi"), Token::StringValue { value: String::from("hi") });
+        assert_eq!(Token::char('c'), Token::CharValue { value: 'c' });
+        assert_eq!(Token::bool(false), Token::BoolValue { value: false });
+        assert_eq!(Token::bytes(b"ab"), Token::BytesValue { value: vec![b'a', b'b'] });
+    }
+
+    #[test]
+    fn test_binary_precedence() {
+        let precedence = |token: Token| token.binary_precedence().unwrap();
+
+        assert!(precedence(Token::Multiply) > precedence(Token::Add));
+        assert_eq!(precedence(Token::Multiply), precedence(Token::Modulo));
+        assert_eq!(precedence(Token::Add), precedence(Token::Subtract));
+        assert!(precedence(Token::Subtract) > precedence(Token::BitwiseLeftShift));
+        assert!(precedence(Token::BitwiseAnd) > precedence(Token::BitwiseXor));
+        assert!(precedence(Token::BitwiseXor) > precedence(Token::BitwiseOr));
+        assert!(precedence(Token::BitwiseOr) > precedence(Token::Less));
+        assert_eq!(precedence(Token::Less), precedence(Token::Equals));
+        assert!(precedence(Token::Equals) > precedence(Token::LogicalAnd));
+        assert!(precedence(Token::LogicalAnd) > precedence(Token::LogicalOr));
+        assert!(precedence(Token::LogicalOr) > precedence(Token::NullCoalesce));
+        assert!(precedence(Token::NullCoalesce) > precedence(Token::RangeExclusive));
+        assert!(precedence(Token::RangeExclusive) > precedence(Token::PipeForward));
+        assert!(precedence(Token::PipeForward) > precedence(Token::Assignment));
+
+        assert_eq!(Token::Not.binary_precedence(), None);
+        assert_eq!(Token::BitwiseNot.binary_precedence(), None);
+        assert_eq!(Token::symbol("x").binary_precedence(), None);
+        assert_eq!(Token::Semicolon.binary_precedence(), None);
+    }
+
+    #[test]
+    fn test_associativity() {
+        assert!(Token::Power.is_right_associative());
+        assert!(Token::Assignment.is_right_associative());
+        assert!(Token::AddAssign.is_right_associative());
+        assert!(Token::Walrus.is_right_associative());
+
+        assert!(!Token::Add.is_right_associative());
+        assert!(!Token::Subtract.is_right_associative());
+        assert!(!Token::Multiply.is_right_associative());
+        assert!(!Token::Divide.is_right_associative());
+        assert!(!Token::LogicalOr.is_right_associative());
+
+        assert!(Token::Power.binary_precedence() > Token::Multiply.binary_precedence());
+    }
+}